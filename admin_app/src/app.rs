@@ -1,4 +1,5 @@
 use crate::error_template::{AppError, ErrorTemplate};
+use crate::identities::IdentitiesPage;
 use leptos::*;
 use leptos_meta::*;
 use leptos_router::*;
@@ -13,7 +14,7 @@ pub fn App() -> impl IntoView {
 
         // injects a stylesheet into the document <head>
         // id=leptos means cargo-leptos will hot-reload this stylesheet
-        <Stylesheet id="leptos" href="/pkg/ory-auth-axum.css"/>
+        <Stylesheet id="leptos" href="/pkg/admin_app.css"/>
 
         // sets the document title
         <Title text="Welcome to Leptos"/>
@@ -30,6 +31,7 @@ pub fn App() -> impl IntoView {
             <main>
                 <Routes>
                     <Route path="" view=HomePage/>
+                    <Route path="/identities" view=IdentitiesPage/>
                 </Routes>
             </main>
         </Router>
@@ -39,14 +41,8 @@ pub fn App() -> impl IntoView {
 /// Renders the home page of your application.
 #[component]
 fn HomePage() -> impl IntoView {
-    // Creates a reactive value to update the button
-    let (count, set_count) = create_signal(0);
-    let on_click = move |_| set_count.update(|count| *count += 1);
-
     view! {
-        <h1>"Welcome to Leptos!"</h1>
-        <button on:click=on_click>"Click Me: " {count}</button>
+        <h1>"Ory Admin"</h1>
+        <a href="identities">Identities</a>
     }
 }
-
-
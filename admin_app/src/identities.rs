@@ -0,0 +1,127 @@
+//! Every identity in Kratos, a page at a time.
+//! https://www.ory.sh/docs/kratos/manage-identities/overview
+#[cfg(feature = "ssr")]
+use crate::kratos_admin::KratosAdmin;
+use leptos::*;
+use ory_kratos_client::models::Identity;
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct IdentityPage {
+    pub identities: Vec<Identity>,
+    /// For the page after this one, none on the last page.
+    pub next_page_token: Option<String>,
+}
+
+/// An empty `credentials_identifier` lists everyone.
+#[server]
+pub async fn list_identities(
+    credentials_identifier: Option<String>,
+    page_token: Option<String>,
+) -> Result<IdentityPage, ServerFnError> {
+    let kratos = expect_context::<KratosAdmin>();
+    let credentials_identifier = credentials_identifier
+        .as_deref()
+        .map(str::trim)
+        .filter(|identifier| !identifier.is_empty());
+    Ok(kratos
+        .list_identities(credentials_identifier, page_token.as_deref())
+        .await?)
+}
+
+/// Kratos' enums (i.e state) as the strings they serialize to.
+fn enum_str<T: Serialize>(value: &T) -> String {
+    serde_json::to_value(value)
+        .ok()
+        .and_then(|value| value.as_str().map(String::from))
+        .unwrap_or_default()
+}
+
+fn identity_row(identity: Identity) -> impl IntoView {
+    let traits = identity
+        .traits
+        .map(|traits| traits.to_string())
+        .unwrap_or_default();
+    let state = identity.state.as_ref().map(enum_str).unwrap_or_default();
+    let verified_addresses = identity
+        .verifiable_addresses
+        .unwrap_or_default()
+        .into_iter()
+        .filter(|address| address.verified)
+        .map(|address| address.value)
+        .collect::<Vec<String>>()
+        .join(", ");
+    view! {
+        <tr>
+            <td>{identity.id}</td>
+            <td><code>{traits}</code></td>
+            <td>{state}</td>
+            <td>{verified_addresses}</td>
+            <td>{identity.created_at.unwrap_or_default()}</td>
+        </tr>
+    }
+}
+
+#[component]
+pub fn IdentitiesPage() -> impl IntoView {
+    // what's typed in the search box, and what we searched for when the form was submitted.
+    let identifier = create_rw_signal(String::new());
+    let search = create_rw_signal(String::new());
+    let page_token = create_rw_signal(None::<String>);
+    // the tokens of the pages before this one, Kratos only tells us about the next one.
+    let previous_tokens = create_rw_signal(Vec::<Option<String>>::new());
+    let page = create_resource(
+        move || (search.get(), page_token.get()),
+        |(search, page_token)| list_identities(Some(search), page_token),
+    );
+    view! {
+        <h1>"Identities"</h1>
+        <form on:submit=move|e|{
+            e.prevent_default();
+            previous_tokens.set(Vec::new());
+            page_token.set(None);
+            search.set(identifier.get_untracked());
+        }>
+            <label>
+                <span>"Credentials Identifier"</span>
+                <input type="search" placeholder="i.e an email, exact match"
+                prop:value=move || identifier.get()
+                on:input=move |ev| identifier.set(event_target_value(&ev))/>
+            </label>
+            <button type="submit">"Search"</button>
+        </form>
+        <Transition fallback=||view!{"Loading Identities"}>
+            <ErrorBoundary fallback=|errors|format!("ERRORS: {:?}",errors.get()).into_view()>
+            {move || page.get().map(|resp| resp.map(|IdentityPage{identities, next_page_token}|{
+                view!{
+                    <table>
+                        <thead>
+                            <tr>
+                                <th>"Id"</th>
+                                <th>"Traits"</th>
+                                <th>"State"</th>
+                                <th>"Verified Addresses"</th>
+                                <th>"Created At"</th>
+                            </tr>
+                        </thead>
+                        <tbody>
+                            {identities.into_iter().map(identity_row).collect_view()}
+                        </tbody>
+                    </table>
+                    <button disabled=move || previous_tokens.with(Vec::is_empty) on:click=move|_|{
+                        if let Some(token) = previous_tokens.try_update(Vec::pop).flatten() {
+                            page_token.set(token);
+                        }
+                    }>"Previous"</button>
+                    {next_page_token.map(|token| view!{
+                        <button on:click=move|_|{
+                            previous_tokens.update(|tokens| tokens.push(page_token.get_untracked()));
+                            page_token.set(Some(token.clone()));
+                        }>"Next"</button>
+                    })}
+                }
+            }))}
+            </ErrorBoundary>
+        </Transition>
+    }
+}
@@ -0,0 +1,103 @@
+//! Kratos' admin api (serve.admin.base_url in kratos.yaml). It has no authentication of it's own, so neither it nor
+//! this app should be reachable from outside.
+use crate::identities::IdentityPage;
+use ory_kratos_client::models::Identity;
+use thiserror::Error;
+use tracing::debug;
+use url::Url;
+
+/// How many identities a page of the identities list has.
+pub const PAGE_SIZE: u32 = 25;
+
+#[derive(Debug, Error)]
+pub enum KratosAdminError {
+    #[error("KRATOS_ADMIN_URL {0} is not a valid url: {1}")]
+    Url(String, url::ParseError),
+    #[error("Couldn't reach Kratos: {0}")]
+    Request(#[from] reqwest::Error),
+    #[error("Kratos answered {status}: {body}")]
+    Status { status: u16, body: String },
+}
+
+#[derive(Clone, Debug)]
+pub struct KratosAdmin {
+    client: reqwest::Client,
+    admin_url: Url,
+}
+
+impl KratosAdmin {
+    /// `KRATOS_ADMIN_URL`, defaults to the port docker compose publishes on the host.
+    pub fn from_env() -> Result<Self, KratosAdminError> {
+        let admin_url = std::env::var("KRATOS_ADMIN_URL")
+            .unwrap_or_else(|_| String::from("http://127.0.0.1:4434/"));
+        let admin_url =
+            Url::parse(&admin_url).map_err(|err| KratosAdminError::Url(admin_url.clone(), err))?;
+        Ok(Self {
+            client: reqwest::Client::new(),
+            admin_url,
+        })
+    }
+
+    /// https://www.ory.sh/docs/reference/api#tag/identity/operation/listIdentities
+    /// `credentials_identifier` is an exact match on any of an identity's identifiers (i.e it's email).
+    /// `page_token` is none for the first page, the next page's token is in the `Link` header.
+    pub async fn list_identities(
+        &self,
+        credentials_identifier: Option<&str>,
+        page_token: Option<&str>,
+    ) -> Result<IdentityPage, KratosAdminError> {
+        let mut url = self
+            .admin_url
+            .join("admin/identities")
+            .expect("Expecting a relative path to join to KRATOS_ADMIN_URL");
+        url.query_pairs_mut()
+            .append_pair("page_size", &PAGE_SIZE.to_string());
+        if let Some(credentials_identifier) = credentials_identifier {
+            url.query_pairs_mut()
+                .append_pair("credentials_identifier", credentials_identifier);
+        }
+        if let Some(page_token) = page_token {
+            url.query_pairs_mut().append_pair("page_token", page_token);
+        }
+        let resp = self
+            .client
+            .get(url)
+            .header("accept", "application/json")
+            .send()
+            .await?;
+        debug!("{:#?}", resp);
+        let status = resp.status().as_u16();
+        if status != 200 {
+            let body = resp.text().await?;
+            return Err(KratosAdminError::Status { status, body });
+        }
+        let next_page_token = resp
+            .headers()
+            .get_all("link")
+            .iter()
+            .filter_map(|link| link.to_str().ok())
+            .find_map(|link| self.next_page_token(link));
+        let identities = resp.json::<Vec<Identity>>().await?;
+        Ok(IdentityPage {
+            identities,
+            next_page_token,
+        })
+    }
+
+    /// i.e `</admin/identities?page_size=25&page_token=abc>; rel="next",</admin/identities?page_size=25>; rel="first"`
+    fn next_page_token(&self, link: &str) -> Option<String> {
+        link.split(',').find_map(|link| {
+            let (target, params) = link.split_once(';')?;
+            if !params.contains("rel=\"next\"") {
+                return None;
+            }
+            let target = target.trim().trim_start_matches('<').trim_end_matches('>');
+            self.admin_url
+                .join(target)
+                .ok()?
+                .query_pairs()
+                .find(|(key, _)| key == "page_token")
+                .map(|(_, page_token)| page_token.into_owned())
+        })
+    }
+}
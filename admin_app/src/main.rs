@@ -1,11 +1,12 @@
 #[cfg(feature = "ssr")]
 #[tokio::main]
 async fn main() {
+    use admin_app::app::*;
+    use admin_app::fileserv::file_and_error_handler;
+    use admin_app::kratos_admin::KratosAdmin;
     use axum::Router;
     use leptos::*;
     use leptos_axum::{generate_route_list, LeptosRoutes};
-    use ory_auth_axum::app::*;
-    use ory_auth_axum::fileserv::file_and_error_handler;
 
     // Setting get_configuration(None) means we'll be using cargo-leptos's env values
     // For deployment these variables are:
@@ -17,9 +18,18 @@ async fn main() {
     let addr = leptos_options.site_addr;
     let routes = generate_route_list(App);
 
+    // Where Kratos' admin api lives, see kratos_admin.rs for the env var.
+    let kratos_admin =
+        KratosAdmin::from_env().unwrap_or_else(|err| panic!("Invalid configuration: {err}"));
+
     // build our application with a route
     let app = Router::new()
-        .leptos_routes(&leptos_options, routes, App)
+        .leptos_routes_with_context(
+            &leptos_options,
+            routes,
+            move || provide_context(kratos_admin.clone()),
+            App,
+        )
         .fallback(file_and_error_handler)
         .with_state(leptos_options);
 
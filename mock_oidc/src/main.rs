@@ -0,0 +1,314 @@
+//! A stand in OIDC provider so social sign in can be tried locally, anyone can sign in as any email.
+//! Kratos (in docker) talks to us at `MOCK_OIDC_ISSUER`, the browser is sent to `MOCK_OIDC_PUBLIC_URL`.
+//! The signing key is made on startup, so nothing here survives a restart (Kratos refetches /jwks).
+use axum::{
+    extract::{Form, Query, State},
+    http::{header, HeaderMap, StatusCode},
+    response::{Html, IntoResponse, Redirect, Response},
+    routing::get,
+    Json, Router,
+};
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use jsonwebtoken::{Algorithm, EncodingKey, Header};
+use rsa::{pkcs1::EncodeRsaPrivateKey, traits::PublicKeyParts, RsaPrivateKey};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::{SystemTime, UNIX_EPOCH},
+};
+use tracing::debug;
+use url::Url;
+
+#[derive(Clone)]
+struct MockProvider {
+    /// providers[].issuer_url in kratos.yaml, what Kratos fetches the discovery document, tokens and keys from.
+    issuer: String,
+    /// Where the browser reaches us, only used for the authorization endpoint.
+    public_url: String,
+    key_id: String,
+    encoding_key: EncodingKey,
+    jwk: serde_json::Value,
+    /// Authorization codes we handed out that haven't been exchanged for tokens yet.
+    codes: Arc<Mutex<HashMap<String, Claims>>>,
+    /// Access tokens for /userinfo
+    access_tokens: Arc<Mutex<HashMap<String, Claims>>>,
+}
+
+/// The id token's claims, see kratos/oidc.mock.jsonnet for how they become traits.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+struct Claims {
+    iss: String,
+    sub: String,
+    aud: String,
+    iat: u64,
+    exp: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    nonce: Option<String>,
+    email: String,
+    email_verified: bool,
+    given_name: String,
+    family_name: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct AuthorizeQuery {
+    client_id: String,
+    redirect_uri: String,
+    state: String,
+    nonce: Option<String>,
+}
+
+/// Our sign in form, the authorization request is carried along in hidden inputs.
+#[derive(Debug, Deserialize)]
+struct SignIn {
+    client_id: String,
+    redirect_uri: String,
+    state: String,
+    nonce: Option<String>,
+    email: String,
+    given_name: String,
+    family_name: String,
+}
+
+/// Client credentials aren't checked, the code is all we need.
+#[derive(Debug, Deserialize)]
+struct TokenRequest {
+    grant_type: String,
+    code: String,
+}
+
+#[derive(Debug, Serialize)]
+struct TokenResponse {
+    access_token: String,
+    token_type: &'static str,
+    expires_in: u64,
+    id_token: String,
+    scope: &'static str,
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|since| since.as_secs())
+        .unwrap_or_default()
+}
+
+fn random_token() -> String {
+    format!("{:032x}", rand::random::<u128>())
+}
+
+/// The form's values go back into attributes.
+fn escape(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('"', "&quot;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+fn oauth_error(status: StatusCode, error: &str) -> Response {
+    (status, Json(serde_json::json!({ "error": error }))).into_response()
+}
+
+/// https://openid.net/specs/openid-connect-discovery-1_0.html#ProviderMetadata
+async fn discovery(State(provider): State<MockProvider>) -> Json<serde_json::Value> {
+    let issuer = &provider.issuer;
+    Json(serde_json::json!({
+        "issuer": issuer,
+        "authorization_endpoint": format!("{}/authorize", provider.public_url),
+        "token_endpoint": format!("{issuer}/token"),
+        "userinfo_endpoint": format!("{issuer}/userinfo"),
+        "jwks_uri": format!("{issuer}/jwks"),
+        "response_types_supported": ["code"],
+        "subject_types_supported": ["public"],
+        "id_token_signing_alg_values_supported": ["RS256"],
+        "scopes_supported": ["openid", "email", "profile"],
+        "token_endpoint_auth_methods_supported": ["client_secret_basic", "client_secret_post"],
+        "claims_supported": ["sub", "email", "email_verified", "given_name", "family_name"],
+    }))
+}
+
+async fn jwks(State(provider): State<MockProvider>) -> Json<serde_json::Value> {
+    Json(serde_json::json!({ "keys": [provider.jwk] }))
+}
+
+async fn authorize_form(Query(query): Query<AuthorizeQuery>) -> Html<String> {
+    let hidden = |name: &str, value: &str| {
+        format!(
+            r#"<input type="hidden" name="{name}" value="{}"/>"#,
+            escape(value)
+        )
+    };
+    Html(format!(
+        r#"<!DOCTYPE html>
+<html>
+  <head><title>Mock OIDC Provider</title></head>
+  <body>
+    <h1>Mock OIDC Provider</h1>
+    <p>Signing in to {client_id}, any email will do.</p>
+    <form method="post" action="/authorize">
+      {client_id_input}
+      {redirect_uri_input}
+      {state_input}
+      {nonce_input}
+      <label>Email <input type="email" name="email" required/></label>
+      <label>First Name <input type="text" name="given_name"/></label>
+      <label>Last Name <input type="text" name="family_name"/></label>
+      <button type="submit">Sign In</button>
+    </form>
+  </body>
+</html>"#,
+        client_id = escape(&query.client_id),
+        client_id_input = hidden("client_id", &query.client_id),
+        redirect_uri_input = hidden("redirect_uri", &query.redirect_uri),
+        state_input = hidden("state", &query.state),
+        nonce_input = query
+            .nonce
+            .as_deref()
+            .map(|nonce| hidden("nonce", nonce))
+            .unwrap_or_default(),
+    ))
+}
+
+/// Sends the browser back to Kratos' callback with a code for whoever they said they were.
+async fn authorize(State(provider): State<MockProvider>, Form(sign_in): Form<SignIn>) -> Response {
+    let Ok(mut redirect_uri) = Url::parse(&sign_in.redirect_uri) else {
+        return oauth_error(StatusCode::BAD_REQUEST, "invalid_request");
+    };
+    let iat = now();
+    let claims = Claims {
+        iss: provider.issuer.clone(),
+        sub: sign_in.email.to_lowercase(),
+        aud: sign_in.client_id,
+        iat,
+        exp: iat + 3600,
+        nonce: sign_in.nonce.filter(|nonce| !nonce.is_empty()),
+        email: sign_in.email,
+        email_verified: true,
+        given_name: sign_in.given_name,
+        family_name: sign_in.family_name,
+    };
+    debug!("{:#?}", claims);
+    let code = random_token();
+    _ = provider
+        .codes
+        .lock()
+        .expect("codes lock poisoned")
+        .insert(code.clone(), claims);
+    redirect_uri
+        .query_pairs_mut()
+        .append_pair("code", &code)
+        .append_pair("state", &sign_in.state);
+    Redirect::to(redirect_uri.as_str()).into_response()
+}
+
+async fn token(
+    State(provider): State<MockProvider>,
+    Form(request): Form<TokenRequest>,
+) -> Response {
+    if request.grant_type != "authorization_code" {
+        return oauth_error(StatusCode::BAD_REQUEST, "unsupported_grant_type");
+    }
+    // codes are single use.
+    let Some(claims) = provider
+        .codes
+        .lock()
+        .expect("codes lock poisoned")
+        .remove(&request.code)
+    else {
+        return oauth_error(StatusCode::BAD_REQUEST, "invalid_grant");
+    };
+    let mut header = Header::new(Algorithm::RS256);
+    header.kid = Some(provider.key_id.clone());
+    let id_token = match jsonwebtoken::encode(&header, &claims, &provider.encoding_key) {
+        Ok(id_token) => id_token,
+        Err(err) => {
+            tracing::error!("Failed to sign the id token: {err}");
+            return oauth_error(StatusCode::INTERNAL_SERVER_ERROR, "server_error");
+        }
+    };
+    let access_token = random_token();
+    _ = provider
+        .access_tokens
+        .lock()
+        .expect("access tokens lock poisoned")
+        .insert(access_token.clone(), claims);
+    Json(TokenResponse {
+        access_token,
+        token_type: "Bearer",
+        expires_in: 3600,
+        id_token,
+        scope: "openid email profile",
+    })
+    .into_response()
+}
+
+async fn userinfo(State(provider): State<MockProvider>, headers: HeaderMap) -> Response {
+    let claims = headers
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .and_then(|access_token| {
+            provider
+                .access_tokens
+                .lock()
+                .expect("access tokens lock poisoned")
+                .get(access_token)
+                .cloned()
+        });
+    match claims {
+        Some(claims) => Json(claims).into_response(),
+        None => oauth_error(StatusCode::UNAUTHORIZED, "invalid_token"),
+    }
+}
+
+#[tokio::main]
+async fn main() {
+    tracing_subscriber::fmt()
+        .with_max_level(tracing::Level::DEBUG)
+        .compact()
+        .init();
+
+    let env = |key: &str, default: &str| std::env::var(key).unwrap_or_else(|_| default.to_string());
+    let addr = env("MOCK_OIDC_ADDR", "0.0.0.0:4446");
+    // Kratos runs in docker, see extra_hosts in docker-compose.yml
+    let issuer = env("MOCK_OIDC_ISSUER", "http://host.docker.internal:4446");
+    let public_url = env("MOCK_OIDC_PUBLIC_URL", "http://127.0.0.1:4446");
+
+    let key =
+        RsaPrivateKey::new(&mut rand::thread_rng(), 2048).expect("Expecting to make an rsa key");
+    let der = key.to_pkcs1_der().expect("Expecting to encode the rsa key");
+    let key_id = format!("mock-oidc-{}", now());
+    let jwk = serde_json::json!({
+        "kty": "RSA",
+        "use": "sig",
+        "alg": "RS256",
+        "kid": key_id,
+        "n": URL_SAFE_NO_PAD.encode(key.n().to_bytes_be()),
+        "e": URL_SAFE_NO_PAD.encode(key.e().to_bytes_be()),
+    });
+    let provider = MockProvider {
+        issuer: issuer.trim_end_matches('/').to_string(),
+        public_url: public_url.trim_end_matches('/').to_string(),
+        key_id,
+        encoding_key: EncodingKey::from_rsa_der(der.as_bytes()),
+        jwk,
+        codes: Arc::default(),
+        access_tokens: Arc::default(),
+    };
+
+    let app = Router::new()
+        .route("/.well-known/openid-configuration", get(discovery))
+        .route("/jwks", get(jwks))
+        .route("/authorize", get(authorize_form).post(authorize))
+        .route("/token", axum::routing::post(token))
+        .route("/userinfo", get(userinfo))
+        .with_state(provider);
+
+    let listener = tokio::net::TcpListener::bind(&addr).await.unwrap();
+    tracing::info!("mock oidc provider listening on http://{addr}, issuer {issuer}");
+    axum::serve(listener, app.into_make_service())
+        .await
+        .unwrap();
+}
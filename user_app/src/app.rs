@@ -1,15 +1,25 @@
 use super::*;
+use crate::devices::DevicesPage;
+use crate::flow_error::ErrorPage;
+use crate::i18n::provide_locale;
 use crate::login::LoginPage;
+use crate::logout::LogoutButton;
+use crate::recovery::RecoveryPage;
 use crate::registration::RegistrationPage;
+use crate::settings::SettingsPage;
 use crate::verification::VerificationPage;
 #[component]
 pub fn App() -> impl IntoView {
     // Provides context that manages stylesheets, titles, meta tags, etc.
     provide_meta_context();
+    let locale = provide_locale();
 
     view! {
 
 
+        // the browser reads the locale back from here when it hydrates.
+        <Html lang=locale.code()/>
+
         // injects a stylesheet into the document <head>
         // id=leptos means cargo-leptos will hot-reload this stylesheet
         <Stylesheet id="leptos" href="/pkg/user_app.css"/>
@@ -29,9 +39,15 @@ pub fn App() -> impl IntoView {
             <main>
                 <Routes>
                     <Route path="" view=HomePage/>
-                    <Route path="/login" view=LoginPage/>
-                    <Route path="/registration" view=RegistrationPage/>
-                    <Route path="/verification" view=VerificationPage/>
+                    // the flow pages set headers while rendering (Kratos' csrf cookie, redirects), so their
+                    // blocking flow resource has to be done before the shell is sent.
+                    <Route path="/login" view=LoginPage ssr=SsrMode::PartiallyBlocked/>
+                    <Route path="/registration" view=RegistrationPage ssr=SsrMode::PartiallyBlocked/>
+                    <Route path="/verification" view=VerificationPage ssr=SsrMode::PartiallyBlocked/>
+                    <Route path="/recovery" view=RecoveryPage ssr=SsrMode::PartiallyBlocked/>
+                    <Route path="/settings" view=SettingsPage ssr=SsrMode::PartiallyBlocked/>
+                    <Route path="/devices" view=DevicesPage/>
+                    <Route path="/error" view=ErrorPage ssr=SsrMode::PartiallyBlocked/>
                 </Routes>
             </main>
         </Router>
@@ -46,92 +62,27 @@ fn HomePage() -> impl IntoView {
         <a href="login">Login</a>
         <br/>
         <a href="registration">Register</a>
+        <br/>
+        <a href="recovery">Forgot Password</a>
+        <br/>
+        <a href="settings">Settings</a>
+        <br/>
+        <a href="devices">Devices</a>
+        <br/>
+        <LogoutButton/>
     }
 }
 
 /*
+requireAuth and requireNoAuth now live in auth.rs (setSession is the Session extractor), detectLanguage is i18n::provide_locale
 
 
 import { getUrlForFlow, isUUID } from "./index"
 import { RouteOptionsCreator } from "./route"
 import { Session } from "@ory/client"
-import { locales } from "@ory/elements-markup"
-import { pick as pickLanguage } from "accept-language-parser"
 import { AxiosError } from "axios"
 import { NextFunction, Request, Response } from "express"
 
-/**
- * Checks the error returned by toSession() and initiates a 2FA flow if necessary
- * or returns false.
- *
- * @internal
- * @param req
- * @param res
- * @param apiBaseUrl
- */
-const maybeInitiate2FA =
-  (req: Request, res: Response, apiBaseUrl: string) => (err: AxiosError) => {
-    // 403 on toSession means that we need to request 2FA
-    if (err.response && err.response.status === 403) {
-      res.redirect(
-        getUrlForFlow(
-          apiBaseUrl,
-          "login",
-          new URLSearchParams({ aal: "aal2", return_to: req.url.toString() }),
-        ),
-      )
-      return true
-    }
-    return false
-  }
-
-/**
- * Adds the session to the request object.
- *
- * @param req
- */
-const addSessionToRequest =
-  (req: Request) =>
-  ({ data: session }: { data: Session }) => {
-    // `whoami` returns the session or an error. We're changing the type here
-    // because express-session is not detected by TypeScript automatically.
-    req.session = session
-  }
-
-/**
- * This middleware requires that the HTTP request has a session.
- * If the session is not present, it will redirect to the login flow.
- *
- * If a session is set but 403 is returned, a 2FA flow will be initiated.
- *
- * @param createHelpers
- */
-export const requireAuth =
-  (createHelpers: RouteOptionsCreator) =>
-  (req: Request, res: Response, next: NextFunction) => {
-    const { frontend, apiBaseUrl } = createHelpers(req, res)
-
-    // when accessing settings with a valid flow id
-    // we allow the settings page to trigger the
-    // login flow on session_aal2_required
-    if (req.url.includes("/settings") && req.query.flow) {
-      if (isUUID.test(req.query.flow.toString())) {
-        next()
-        return
-      }
-    }
-    frontend
-      .toSession({ cookie: req.header("cookie") })
-      .then(addSessionToRequest(req))
-      .then(() => next())
-      .catch((err: AxiosError) => {
-        if (!maybeInitiate2FA(req, res, apiBaseUrl)(err)) {
-          res.redirect(getUrlForFlow(apiBaseUrl, "login"))
-          return
-        }
-      })
-  }
-
 export const addFavicon =
   (createHelpers: RouteOptionsCreator) =>
   (req: Request, res: Response, next: NextFunction) => {
@@ -142,62 +93,4 @@ export const addFavicon =
     next()
   }
 
-/**
- * Sets the session in the request. If no session is found,
- * the request still succeeds.
- *
- * If a session is set but 403 is returned, a 2FA flow will be initiated.
- *
- * @param createHelpers
- */
-export const setSession =
-  (createHelpers: RouteOptionsCreator) =>
-  (req: Request, res: Response, next: NextFunction) => {
-    const { frontend, apiBaseUrl } = createHelpers(req, res)
-    frontend
-      .toSession({ cookie: req.header("cookie") })
-      .then(addSessionToRequest(req))
-      .catch(maybeInitiate2FA(req, res, apiBaseUrl))
-      .then(() => next())
-  }
-
-/**
- * This middleware requires that the HTTP request has no session.
- * If the session is present, it will redirect to the home page.
- *
- * @param createHelpers
- */
-export const requireNoAuth =
-  (createHelpers: RouteOptionsCreator) =>
-  (req: Request, res: Response, next: NextFunction) => {
-    const { frontend } = createHelpers(req, res)
-    frontend
-      .toSession({ cookie: req.header("cookie") })
-      .then(() => {
-        res.redirect("welcome")
-      })
-      .catch(() => {
-        next()
-      })
-  }
-
-/**
- * Detects the language of the user and sets it in the response locals.
- *
- * @param req
- * @param res
- * @param next
- */
-export const detectLanguage = (
-  req: Request,
-  res: Response,
-  next: NextFunction,
-) => {
-  res.locals.lang = pickLanguage(
-    Object.keys(locales),
-    req.header("Accept-Language") || "en",
-  )
-  next()
-}
-
 */
@@ -0,0 +1,295 @@
+//! Rust versions of the express middleware from Ory's examples (requireAuth, requireNoAuth). There's no setSession,
+//! handlers that want the session if there is one use the [Session] extractor.
+//! Routes declare what they need in [AuthRequirement::for_path] and [auth_guard] enforces it before the page renders.
+use crate::config::AppConfig;
+use crate::kratos_client::KratosClient;
+use crate::kratos_error::KratosError;
+use crate::session::{Session, SessionError};
+use axum::{
+    extract::Request,
+    http::Uri,
+    middleware::Next,
+    response::{IntoResponse, Redirect, Response},
+};
+use axum_extra::extract::CookieJar;
+use leptos::{expect_context, use_context};
+use std::time::Duration;
+use time::{format_description::well_known::Rfc3339, OffsetDateTime};
+use tracing::debug;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum AuthRequirement {
+    /// Logged out users are sent to the login flow, and back here afterwards.
+    Session,
+    /// A session with a second factor, aal1 sessions are sent to the login page's second factor step first.
+    Aal2,
+    /// Logged in users are sent home, there's no point logging in twice.
+    NoSession,
+    /// Anyone, we don't ask Kratos about the session.
+    Any,
+}
+
+impl AuthRequirement {
+    pub fn for_path(path: &str) -> Self {
+        match path {
+            // whoami already asks for the highest aal the identity has (session.whoami.required_aal in kratos.yaml).
+            "/settings" | "/devices" => AuthRequirement::Session,
+            "/login" | "/registration" => AuthRequirement::NoSession,
+            _ => AuthRequirement::Any,
+        }
+    }
+}
+
+/// Layer with `axum::middleware::from_fn(auth_guard)`.
+pub async fn auth_guard(req: Request, next: Next) -> Response {
+    match AuthRequirement::for_path(req.uri().path()) {
+        AuthRequirement::Session => require_auth(req, next).await,
+        AuthRequirement::Aal2 => require_aal2(req, next).await,
+        AuthRequirement::NoSession => require_no_auth(req, next).await,
+        AuthRequirement::Any => next.run(req).await,
+    }
+}
+
+/// Our login page's second factor step (see [crate::login::init_login]), coming back to `return_to` afterwards.
+pub fn step_up_url(config: &AppConfig, return_to: &str) -> String {
+    let mut url = config.base("login");
+    url.query_pairs_mut()
+        .append_pair("aal", "aal2")
+        .append_pair("return_to", return_to);
+    url.to_string()
+}
+
+/// Our login page's refresh login (see [crate::login::init_login]), the user enters their password again and comes
+/// back to `return_to`.
+pub fn refresh_login_url(config: &AppConfig, return_to: &str) -> String {
+    let mut url = config.base("login");
+    url.query_pairs_mut()
+        .append_pair("refresh", "true")
+        .append_pair("return_to", return_to);
+    url.to_string()
+}
+
+/// Kratos' login browser flow (or our second factor step), coming back to `uri` (the request's) afterwards.
+fn login_flow_url(config: &AppConfig, uri: &Uri, aal2: bool) -> String {
+    // uri is the path and query of the request, base needs it relative.
+    let return_to = config.base(uri.to_string().trim_start_matches('/'));
+    if aal2 {
+        return step_up_url(config, return_to.as_str());
+    }
+    let mut url = config.kratos_public("self-service/login/browser");
+    url.query_pairs_mut()
+        .append_pair("return_to", return_to.as_str());
+    url.to_string()
+}
+
+/// Our second factor step for an aal1 session, none when the session already has a second factor.
+pub fn aal2_redirect(config: &AppConfig, session: &Session, uri: &Uri) -> Option<String> {
+    (!session.is_aal2()).then(|| login_flow_url(config, uri, true))
+}
+
+/// main.rs adds it as an Extension.
+fn kratos(req: &Request) -> KratosClient {
+    req.extensions()
+        .get::<KratosClient>()
+        .cloned()
+        .expect("Expecting KratosClient in the request extensions")
+}
+
+/// Checks the error returned by whoami and sends the browser to a 2FA login if that's the problem.
+fn maybe_initiate_2fa(req: &Request, err: &SessionError) -> Option<Response> {
+    match err {
+        SessionError::Aal2Required { .. } => Some(
+            Redirect::to(&login_flow_url(kratos(req).config(), req.uri(), true)).into_response(),
+        ),
+        _ => None,
+    }
+}
+
+fn is_uuid(value: &str) -> bool {
+    value.len() == 36
+        && value.char_indices().all(|(i, c)| match i {
+            8 | 13 | 18 | 23 => c == '-',
+            _ => c.is_ascii_hexdigit(),
+        })
+}
+
+fn has_query_param(req: &Request, key: &str) -> Option<String> {
+    url::form_urlencoded::parse(req.uri().query().unwrap_or_default().as_bytes())
+        .find(|(k, _)| k == key)
+        .map(|(_, v)| v.into_owned())
+}
+
+/// Requires a session, if there isn't one we redirect to the login flow.
+/// If a session is set but whoami returns 403, a 2FA flow will be initiated.
+pub async fn require_auth(mut req: Request, next: Next) -> Response {
+    // when accessing settings with a valid flow id
+    // we allow the settings page to trigger the
+    // login flow on session_aal2_required
+    if req.uri().path() == "/settings"
+        && has_query_param(&req, "flow").is_some_and(|flow| is_uuid(&flow))
+    {
+        return next.run(req).await;
+    }
+    match Session::whoami(&kratos(&req), &CookieJar::from_headers(req.headers())).await {
+        Ok(session) => {
+            req.extensions_mut().insert(session);
+            next.run(req).await
+        }
+        Err(err) => {
+            debug!("require_auth: {err}");
+            maybe_initiate_2fa(&req, &err).unwrap_or_else(|| {
+                Redirect::to(&login_flow_url(kratos(&req).config(), req.uri(), false))
+                    .into_response()
+            })
+        }
+    }
+}
+
+/// Requires a session with a second factor, an aal1 session is sent to the second factor step and no session at all
+/// to the login flow.
+pub async fn require_aal2(mut req: Request, next: Next) -> Response {
+    match Session::whoami(&kratos(&req), &CookieJar::from_headers(req.headers())).await {
+        Ok(session) => match aal2_redirect(kratos(&req).config(), &session, req.uri()) {
+            Some(step_up) => Redirect::to(&step_up).into_response(),
+            None => {
+                req.extensions_mut().insert(session);
+                next.run(req).await
+            }
+        },
+        Err(err) => {
+            debug!("require_aal2: {err}");
+            maybe_initiate_2fa(&req, &err).unwrap_or_else(|| {
+                Redirect::to(&login_flow_url(kratos(&req).config(), req.uri(), false))
+                    .into_response()
+            })
+        }
+    }
+}
+
+/// Requires there be no session, if there is one we redirect home.
+pub async fn require_no_auth(req: Request, next: Next) -> Response {
+    // stepping up to aal2 or refreshing a session both need the login page while logged in,
+    // and if Kratos already made a flow for us it decided the user may have one.
+    if ["aal", "refresh", "flow"]
+        .into_iter()
+        .any(|key| has_query_param(&req, key).is_some())
+    {
+        return next.run(req).await;
+    }
+    match Session::whoami(&kratos(&req), &CookieJar::from_headers(req.headers())).await {
+        Ok(_) => Redirect::to("/").into_response(),
+        Err(_) => next.run(req).await,
+    }
+}
+
+/// The page a server function was called from, to come back to after a login. The server function's own url is no
+/// place to come back to.
+fn calling_page(config: &AppConfig) -> url::Url {
+    use_context::<http::request::Parts>()
+        .and_then(|parts| {
+            parts
+                .headers
+                .get("referer")
+                .and_then(|referer| referer.to_str().ok())
+                .and_then(|referer| config.allowed_return_to(referer))
+        })
+        .unwrap_or_else(|| config.base(""))
+}
+
+/// The session for server functions, like [auth_guard] does for pages. whoami asks for the highest aal the identity
+/// has (session.whoami.required_aal in kratos.yaml), so an aal1 session of an identity with a second factor is a
+/// [KratosError::Aal2Required] pointing at our second factor step, that comes back to the page the server function was
+/// called from. Identities without a second factor get by with aal1.
+pub async fn require_session() -> Result<Session, KratosError> {
+    let kratos = expect_context::<KratosClient>();
+    let cookie_jar = leptos_axum::extract::<CookieJar>().await?;
+    Session::whoami(&kratos, &cookie_jar)
+        .await
+        .map_err(|err| match err {
+            SessionError::Aal2Required { .. } => KratosError::Aal2Required {
+                redirect_browser_to: Some(step_up_url(
+                    kratos.config(),
+                    calling_page(kratos.config()).as_str(),
+                )),
+            },
+            err => err.into_kratos_error(),
+        })
+}
+
+/// Our privileged_session_max_age for server functions, Kratos only enforces it's own on the settings flow.
+/// A session that logged in longer than `max_age` ago is a [KratosError::RefreshRequired] pointing at a refresh login
+/// that comes back to the page the server function was called from. See [require_session] for the aal.
+pub async fn require_recent_auth(max_age: Duration) -> Result<Session, KratosError> {
+    let session = require_session().await?;
+    let authenticated_at = session
+        .authenticated_at
+        .as_deref()
+        .and_then(|authenticated_at| OffsetDateTime::parse(authenticated_at, &Rfc3339).ok());
+    let recent = authenticated_at
+        .is_some_and(|authenticated_at| OffsetDateTime::now_utc() - authenticated_at <= max_age);
+    if recent {
+        return Ok(session);
+    }
+    debug!("require_recent_auth: authenticated at {authenticated_at:?}, more than {max_age:?} ago");
+    let kratos = expect_context::<KratosClient>();
+    let return_to = calling_page(kratos.config());
+    Err(KratosError::RefreshRequired {
+        redirect_browser_to: Some(refresh_login_url(kratos.config(), return_to.as_str())),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn session(aal: &str) -> Session {
+        Session(
+            serde_json::from_value(serde_json::json!({
+                "id": "9f425a8d-7efc-4768-8f23-7647a74fdf13",
+                "authenticator_assurance_level": aal,
+                "identity": {
+                    "id": "5b0a3bd4-2e1c-4a9a-9c2b-0c8a3f1a0d21",
+                    "schema_id": "default",
+                    "schema_url": "http://127.0.0.1:4433/schemas/ZGVmYXVsdA",
+                    "traits": {"email": "user@example.com"},
+                },
+            }))
+            .unwrap(),
+        )
+    }
+
+    #[test]
+    fn routes_declare_what_they_need() {
+        assert_eq!(
+            AuthRequirement::for_path("/devices"),
+            AuthRequirement::Session
+        );
+        assert_eq!(
+            AuthRequirement::for_path("/settings"),
+            AuthRequirement::Session
+        );
+        assert_eq!(
+            AuthRequirement::for_path("/login"),
+            AuthRequirement::NoSession
+        );
+        assert_eq!(AuthRequirement::for_path("/"), AuthRequirement::Any);
+    }
+
+    #[test]
+    fn aal1_sessions_are_sent_to_the_second_factor_step() {
+        let uri = Uri::from_static("/devices?tab=all");
+        assert_eq!(
+            aal2_redirect(&AppConfig::default(), &session("aal1"), &uri).as_deref(),
+            Some("http://localhost:3000/login?aal=aal2&return_to=http%3A%2F%2Flocalhost%3A3000%2Fdevices%3Ftab%3Dall")
+        );
+    }
+
+    #[test]
+    fn aal2_sessions_pass() {
+        let uri = Uri::from_static("/devices");
+        assert_eq!(
+            aal2_redirect(&AppConfig::default(), &session("aal2"), &uri),
+            None
+        );
+    }
+}
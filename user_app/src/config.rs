@@ -0,0 +1,317 @@
+//! Where Kratos (and we) live. Loaded once in main.rs and provided to server functions as Leptos context,
+//! and to axum extractors/middleware as a request extension.
+use serde::{Deserialize, Deserializer};
+use std::path::PathBuf;
+use std::time::Duration;
+use thiserror::Error;
+use url::Url;
+
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+pub struct AppConfig {
+    /// serve.public.base_url in kratos.yaml
+    pub kratos_public_url: Url,
+    /// serve.admin.base_url in kratos.yaml
+    pub kratos_admin_url: Url,
+    /// Where this app is served from, i.e the origin of the ui_urls in kratos.yaml
+    pub base_url: Url,
+    /// selfservice.allowed_return_urls in kratos.yaml
+    pub allowed_return_urls: Vec<Url>,
+    /// selfservice.flows.logout.after.default_browser_return_url in kratos.yaml, where the browser goes after logging
+    /// out. It's the logout flow's return_to, so it has to be one of the allowed_return_urls.
+    pub after_logout_url: Url,
+    /// selfservice.flows.settings.privileged_session_max_age in kratos.yaml (i.e "15m"), for our own privileged
+    /// server functions, see `auth::require_recent_auth`.
+    #[serde(deserialize_with = "deserialize_duration")]
+    pub privileged_session_max_age: Duration,
+}
+
+#[derive(Debug, Error)]
+pub enum ConfigError {
+    #[error("Couldn't read config file {0}: {1}")]
+    Io(PathBuf, std::io::Error),
+    #[error("Couldn't parse config file {0}: {1}")]
+    Json(PathBuf, serde_json::Error),
+    #[error("{0} is not a valid url: {1}")]
+    Url(&'static str, url::ParseError),
+    #[error("{0} must be an http(s) url, got {1}")]
+    Scheme(&'static str, Url),
+    #[error(
+        "allowed_return_urls can't be empty, Kratos needs to be able to send users back to {0}"
+    )]
+    NoReturnUrls(Url),
+    #[error("{0} {1} isn't under any of the allowed_return_urls")]
+    ReturnUrlNotAllowed(&'static str, Url),
+    #[error("{0} must be a duration like 15m or 1h30m, got {1}")]
+    Duration(&'static str, String),
+}
+
+impl Default for AppConfig {
+    /// Matches kratos/kratos.yaml when running Kratos with docker compose and us with cargo leptos.
+    fn default() -> Self {
+        Self {
+            kratos_public_url: Url::parse("http://localhost:4433/").unwrap(),
+            kratos_admin_url: Url::parse("http://localhost:4434/").unwrap(),
+            base_url: Url::parse("http://localhost:3000/").unwrap(),
+            allowed_return_urls: vec![Url::parse("http://localhost:3000/").unwrap()],
+            after_logout_url: Url::parse("http://localhost:3000/login").unwrap(),
+            privileged_session_max_age: Duration::from_secs(15 * 60),
+        }
+    }
+}
+
+impl AppConfig {
+    /// Starts from the json file at `USER_APP_CONFIG` (or the defaults) and then applies
+    /// `KRATOS_PUBLIC_URL`, `KRATOS_ADMIN_URL`, `BASE_URL`, `ALLOWED_RETURN_URLS` (comma separated),
+    /// `AFTER_LOGOUT_URL` and `PRIVILEGED_SESSION_MAX_AGE` on top.
+    pub fn load() -> Result<Self, ConfigError> {
+        let mut config = match std::env::var_os("USER_APP_CONFIG") {
+            Some(path) => {
+                let path = PathBuf::from(path);
+                let file = std::fs::read_to_string(&path)
+                    .map_err(|err| ConfigError::Io(path.clone(), err))?;
+                serde_json::from_str(&file).map_err(|err| ConfigError::Json(path, err))?
+            }
+            None => AppConfig::default(),
+        };
+        let env_url = |key: &'static str| -> Result<Option<Url>, ConfigError> {
+            std::env::var(key)
+                .ok()
+                .map(|value| Url::parse(&value).map_err(|err| ConfigError::Url(key, err)))
+                .transpose()
+        };
+        if let Some(url) = env_url("KRATOS_PUBLIC_URL")? {
+            config.kratos_public_url = url;
+        }
+        if let Some(url) = env_url("KRATOS_ADMIN_URL")? {
+            config.kratos_admin_url = url;
+        }
+        if let Some(url) = env_url("BASE_URL")? {
+            config.base_url = url;
+        }
+        if let Ok(urls) = std::env::var("ALLOWED_RETURN_URLS") {
+            config.allowed_return_urls = urls
+                .split(',')
+                .map(|url| {
+                    Url::parse(url.trim())
+                        .map_err(|err| ConfigError::Url("ALLOWED_RETURN_URLS", err))
+                })
+                .collect::<Result<Vec<Url>, ConfigError>>()?;
+        }
+        if let Some(url) = env_url("AFTER_LOGOUT_URL")? {
+            config.after_logout_url = url;
+        }
+        if let Ok(max_age) = std::env::var("PRIVILEGED_SESSION_MAX_AGE") {
+            config.privileged_session_max_age = parse_duration(&max_age)
+                .ok_or(ConfigError::Duration("PRIVILEGED_SESSION_MAX_AGE", max_age))?;
+        }
+        config.validate()
+    }
+
+    /// Checks every url is http(s) and makes sure our base urls end in a slash so [Url::join] appends to them.
+    pub fn validate(mut self) -> Result<Self, ConfigError> {
+        for (key, url) in [
+            ("kratos_public_url", &mut self.kratos_public_url),
+            ("kratos_admin_url", &mut self.kratos_admin_url),
+            ("base_url", &mut self.base_url),
+        ] {
+            if !matches!(url.scheme(), "http" | "https") {
+                return Err(ConfigError::Scheme(key, url.clone()));
+            }
+            if !url.path().ends_with('/') {
+                let path = format!("{}/", url.path());
+                url.set_path(&path);
+            }
+        }
+        if let Some(url) = self
+            .allowed_return_urls
+            .iter()
+            .find(|url| !matches!(url.scheme(), "http" | "https"))
+        {
+            return Err(ConfigError::Scheme("allowed_return_urls", url.clone()));
+        }
+        if self.allowed_return_urls.is_empty() {
+            return Err(ConfigError::NoReturnUrls(self.base_url));
+        }
+        if self
+            .allowed_return_to(self.after_logout_url.as_str())
+            .is_none()
+        {
+            return Err(ConfigError::ReturnUrlNotAllowed(
+                "after_logout_url",
+                self.after_logout_url,
+            ));
+        }
+        // every session would need a refresh login for everything privileged.
+        if self.privileged_session_max_age.is_zero() {
+            return Err(ConfigError::Duration(
+                "privileged_session_max_age",
+                String::from("0s"),
+            ));
+        }
+        Ok(self)
+    }
+
+    /// i.e `config.kratos_public("self-service/login/browser")`
+    pub fn kratos_public(&self, path: &str) -> Url {
+        self.kratos_public_url
+            .join(path)
+            .expect("Expecting a relative path to join to kratos_public_url")
+    }
+
+    pub fn kratos_admin(&self, path: &str) -> Url {
+        self.kratos_admin_url
+            .join(path)
+            .expect("Expecting a relative path to join to kratos_admin_url")
+    }
+
+    /// A url on our app, i.e `config.base("settings")`
+    pub fn base(&self, path: &str) -> Url {
+        self.base_url
+            .join(path)
+            .expect("Expecting a relative path to join to base_url")
+    }
+
+    /// The same check Kratos does against selfservice.allowed_return_urls: same origin and the path starts with
+    /// an allowed url's path. Relative return_to's are on our app.
+    pub fn allowed_return_to(&self, return_to: &str) -> Option<Url> {
+        // joining an absolute url (or //host) replaces the base entirely, so it's checked like any other.
+        let url = self.base_url.join(return_to).ok()?;
+        self.allowed_return_urls
+            .iter()
+            .any(|allowed| {
+                allowed.origin() == url.origin() && url.path().starts_with(allowed.path())
+            })
+            .then_some(url)
+    }
+}
+
+/// Kratos' (Go's) durations in hours, minutes and seconds, i.e `15m`, `1h30m` or `90s`.
+fn parse_duration(value: &str) -> Option<Duration> {
+    let value = value.trim();
+    let mut secs = 0;
+    let mut digits = String::new();
+    for c in value.chars() {
+        if c.is_ascii_digit() {
+            digits.push(c);
+            continue;
+        }
+        let amount = digits.parse::<u64>().ok()?;
+        digits.clear();
+        secs += amount
+            * match c {
+                'h' => 60 * 60,
+                'm' => 60,
+                's' => 1,
+                _ => return None,
+            };
+    }
+    // a bare number has no unit, Kratos won't take it either.
+    (!value.is_empty() && digits.is_empty()).then(|| Duration::from_secs(secs))
+}
+
+fn deserialize_duration<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Duration, D::Error> {
+    let value = String::deserialize(deserializer)?;
+    parse_duration(&value)
+        .ok_or_else(|| serde::de::Error::custom(format!("{value} is not a duration like 15m")))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn allowed_return_to(return_to: &str) -> Option<String> {
+        AppConfig {
+            allowed_return_urls: vec![
+                Url::parse("http://localhost:3000/").unwrap(),
+                Url::parse("https://app.example.com/account/").unwrap(),
+            ],
+            ..AppConfig::default()
+        }
+        .validate()
+        .unwrap()
+        .allowed_return_to(return_to)
+        .map(|url| url.to_string())
+    }
+
+    #[test]
+    fn relative_paths_are_on_our_app() {
+        assert_eq!(
+            allowed_return_to("/settings").as_deref(),
+            Some("http://localhost:3000/settings")
+        );
+        assert_eq!(
+            allowed_return_to("settings?flow=1").as_deref(),
+            Some("http://localhost:3000/settings?flow=1")
+        );
+    }
+
+    #[test]
+    fn allowed_urls_and_paths_under_them() {
+        assert_eq!(
+            allowed_return_to("http://localhost:3000/devices").as_deref(),
+            Some("http://localhost:3000/devices")
+        );
+        assert_eq!(
+            allowed_return_to("https://app.example.com/account/profile").as_deref(),
+            Some("https://app.example.com/account/profile")
+        );
+        assert_eq!(allowed_return_to("https://app.example.com/other"), None);
+    }
+
+    #[test]
+    fn protocol_relative_urls_are_other_hosts() {
+        assert_eq!(allowed_return_to("//evil.com"), None);
+        assert_eq!(allowed_return_to("//evil.com/settings"), None);
+        // browsers (and the url crate) read a backslash as a slash.
+        assert_eq!(allowed_return_to("/\\evil.com"), None);
+    }
+
+    #[test]
+    fn javascript_urls() {
+        assert_eq!(allowed_return_to("javascript:alert(1)"), None);
+        assert_eq!(allowed_return_to("JavaScript:alert(document.cookie)"), None);
+    }
+
+    #[test]
+    fn other_origins() {
+        assert_eq!(allowed_return_to("https://evil.com/"), None);
+        assert_eq!(allowed_return_to("http://localhost:3001/"), None);
+        assert_eq!(allowed_return_to("https://localhost:3000/"), None);
+        assert_eq!(allowed_return_to("http://localhost.evil.com:3000/"), None);
+    }
+
+    #[test]
+    fn kratos_durations() {
+        assert_eq!(parse_duration("15m"), Some(Duration::from_secs(15 * 60)));
+        assert_eq!(parse_duration("1h30m"), Some(Duration::from_secs(90 * 60)));
+        assert_eq!(parse_duration("90s"), Some(Duration::from_secs(90)));
+        assert_eq!(parse_duration("15"), None);
+        assert_eq!(parse_duration("15d"), None);
+        assert_eq!(parse_duration("m"), None);
+        assert_eq!(parse_duration(""), None);
+    }
+
+    #[test]
+    fn privileged_session_max_age_cant_be_zero() {
+        let config = AppConfig {
+            privileged_session_max_age: Duration::ZERO,
+            ..AppConfig::default()
+        };
+        assert!(matches!(
+            config.validate(),
+            Err(ConfigError::Duration("privileged_session_max_age", _))
+        ));
+    }
+
+    #[test]
+    fn after_logout_url_must_be_allowed() {
+        let config = AppConfig {
+            after_logout_url: Url::parse("https://evil.com/login").unwrap(),
+            ..AppConfig::default()
+        };
+        assert!(matches!(
+            config.validate(),
+            Err(ConfigError::ReturnUrlNotAllowed("after_logout_url", _))
+        ));
+    }
+}
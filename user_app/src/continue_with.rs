@@ -0,0 +1,179 @@
+//! With `feature_flags.use_continue_with_transitions` Kratos tells us what comes after a successful submission
+//! (`continue_with` on the success payload) instead of leaving it to us to guess, we follow it exactly.
+use super::*;
+
+#[cfg(feature = "ssr")]
+use crate::config::AppConfig;
+#[cfg(feature = "ssr")]
+use crate::kratos_client::KratosClient;
+#[cfg(feature = "ssr")]
+use crate::kratos_error::KratosError;
+use crate::kratos_utils::ExternalRedirect;
+#[cfg(feature = "ssr")]
+use tracing::debug;
+
+/// https://www.ory.sh/docs/reference/api#tag/frontend/operation/updateRegistrationFlow (continue_with)
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "action", rename_all = "snake_case")]
+pub enum ContinueWith {
+    /// The verification flow Kratos created for the new identity (the `show_verification_ui` hook).
+    ShowVerificationUi {
+        flow: ContinueWithFlow,
+    },
+    ShowSettingsUi {
+        flow: ContinueWithFlow,
+    },
+    ShowRecoveryUi {
+        flow: ContinueWithFlow,
+    },
+    /// Only issued for api flows, the browser gets a session cookie instead.
+    SetOrySessionToken {
+        ory_session_token: String,
+    },
+    RedirectBrowserTo {
+        redirect_browser_to: String,
+    },
+    /// Whatever newer versions of Kratos add.
+    #[serde(other)]
+    Unknown,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ContinueWithFlow {
+    pub id: String,
+    /// The flow's ui_url with ?flow=FLOW_ID when Kratos knows it.
+    pub url: Option<String>,
+    pub verifiable_address: Option<String>,
+}
+
+/// Where a page goes once Kratos is done with it's flow.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Destination {
+    /// A path on our app, i.e /verification?flow=FLOW_ID
+    Route(String),
+    /// Anywhere else, i.e Kratos itself or an allowed return_to on another app.
+    Url(String),
+}
+
+impl Destination {
+    /// What to put in the browser's address bar.
+    pub fn href(&self) -> String {
+        match self {
+            Destination::Route(path) | Destination::Url(path) => path.clone(),
+        }
+    }
+
+    /// Kratos hands out absolute urls, the ones on our origin become routes so we can navigate without a page load.
+    #[cfg(feature = "ssr")]
+    pub fn from_url(url: &str, config: &AppConfig) -> Self {
+        match url::Url::parse(url) {
+            Ok(parsed) if parsed.origin() == config.base_url.origin() => {
+                Destination::Route(match parsed.query() {
+                    Some(query) => format!("{}?{}", parsed.path(), query),
+                    None => parsed.path().to_string(),
+                })
+            }
+            Ok(_) => Destination::Url(url.to_string()),
+            // already relative, so it's ours.
+            Err(_) => Destination::Route(url.to_string()),
+        }
+    }
+}
+
+impl IntoView for Destination {
+    fn into_view(self) -> View {
+        match self {
+            Destination::Route(path) => view! {<Redirect path/>}.into_view(),
+            Destination::Url(url) => view! {<ExternalRedirect url/>}.into_view(),
+        }
+    }
+}
+
+#[cfg(feature = "ssr")]
+impl ContinueWith {
+    /// The `continue_with` of a success payload, empty if there isn't one.
+    pub fn parse(value: &serde_json::Value) -> Vec<Self> {
+        value
+            .get("continue_with")
+            .cloned()
+            .and_then(|continue_with| serde_json::from_value::<Vec<Self>>(continue_with).ok())
+            .unwrap_or_default()
+    }
+
+    /// Kratos lists them in the order it wants them followed, the first one a browser can follow wins.
+    pub fn destination(continue_with: &[Self], config: &AppConfig) -> Option<Destination> {
+        continue_with.iter().find_map(|item| match item {
+            ContinueWith::ShowVerificationUi { flow } => {
+                Some(flow_destination(flow, "verification", config))
+            }
+            ContinueWith::ShowSettingsUi { flow } => {
+                Some(flow_destination(flow, "settings", config))
+            }
+            ContinueWith::ShowRecoveryUi { flow } => {
+                Some(flow_destination(flow, "recovery", config))
+            }
+            ContinueWith::RedirectBrowserTo {
+                redirect_browser_to,
+            } => Some(Destination::from_url(redirect_browser_to, config)),
+            ContinueWith::SetOrySessionToken { .. } | ContinueWith::Unknown => {
+                debug!("skipping {:?}", item);
+                None
+            }
+        })
+    }
+}
+
+/// Where to go after a successful submission: Kratos' continue_with, or else the flow's return_to (checked again,
+/// it's been through the browser), or else home.
+#[cfg(feature = "ssr")]
+pub fn after_success(
+    kratos: &KratosClient,
+    value: &serde_json::Value,
+    return_to: Option<String>,
+) -> Destination {
+    continue_to(kratos, value, return_to).unwrap_or(Destination::Route(String::from("/")))
+}
+
+/// Settings and verification answer a finished submission with the flow itself, in `finished_state`, so the user
+/// sees Kratos' "saved" or "verified" message. It's only left when there's somewhere to continue to, the return_to
+/// the flow was made with included.
+#[cfg(feature = "ssr")]
+pub fn after_finished_flow<F: Serialize>(
+    kratos: &KratosClient,
+    flow: &F,
+    finished_state: &str,
+    return_to: Option<String>,
+) -> Result<Option<Destination>, KratosError> {
+    let value = serde_json::to_value(flow)?;
+    if value.get("state").and_then(|state| state.as_str()) != Some(finished_state) {
+        return Ok(None);
+    }
+    let return_to = return_to.or_else(|| {
+        value
+            .get("return_to")
+            .and_then(|return_to| return_to.as_str())
+            .map(String::from)
+    });
+    Ok(continue_to(kratos, &value, return_to))
+}
+
+#[cfg(feature = "ssr")]
+fn continue_to(
+    kratos: &KratosClient,
+    value: &serde_json::Value,
+    return_to: Option<String>,
+) -> Option<Destination> {
+    ContinueWith::destination(&ContinueWith::parse(value), kratos.config()).or_else(|| {
+        return_to
+            .and_then(|return_to| kratos.checked_return_to(&return_to).ok())
+            .map(|url| Destination::from_url(url.as_str(), kratos.config()))
+    })
+}
+
+#[cfg(feature = "ssr")]
+fn flow_destination(flow: &ContinueWithFlow, page: &str, config: &AppConfig) -> Destination {
+    match &flow.url {
+        Some(url) => Destination::from_url(url, config),
+        None => Destination::Route(format!("/{page}?flow={}", flow.id)),
+    }
+}
@@ -0,0 +1,177 @@
+//! Where the identity is logged in, one Kratos session per browser/device, and signing them out.
+//! https://www.ory.sh/docs/kratos/session-management/list-revoke-get-sessions
+use super::*;
+
+#[cfg(feature = "ssr")]
+use crate::auth::{require_recent_auth, require_session};
+#[cfg(feature = "ssr")]
+use crate::kratos_client::{forwarded_cookies, kratos_json, kratos_ok, KratosClient};
+use crate::kratos_error::KratosError;
+use crate::kratos_utils::{enum_str, ExternalRedirect};
+
+use ory_kratos_client::models::Session;
+#[cfg(feature = "ssr")]
+use tracing::debug;
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Sessions {
+    /// The session of the browser looking at the page.
+    pub current: Session,
+    /// Every other active session of the same identity.
+    pub others: Vec<Session>,
+}
+
+/// Kratos' answer to revoking the other sessions.
+#[cfg(feature = "ssr")]
+#[derive(Debug, Deserialize)]
+struct RevokedCount {
+    count: i64,
+}
+
+/// https://www.ory.sh/docs/reference/api#tag/frontend/operation/listMySessions
+/// Kratos leaves the current session out, so we get it from whoami.
+#[tracing::instrument]
+#[server]
+pub async fn list_sessions() -> Result<Sessions, ServerFnError<KratosError>> {
+    let current = require_session().await?.0;
+    let kratos = expect_context::<KratosClient>();
+    let resp = kratos
+        .get("sessions")
+        .header("cookie", forwarded_cookies().await?)
+        .send()
+        .await
+        .map_err(KratosError::from)?;
+    let others = kratos_json::<Vec<Session>>(resp).await?;
+    Ok(Sessions { current, others })
+}
+
+/// https://www.ory.sh/docs/reference/api#tag/frontend/operation/disableMySession
+/// Kratos won't revoke the current session this way, that's what logging out is for.
+#[tracing::instrument]
+#[server]
+pub async fn revoke_session(session_id: String) -> Result<(), ServerFnError<KratosError>> {
+    let kratos = expect_context::<KratosClient>();
+    // signing other devices out is as privileged as changing the password.
+    require_recent_auth(kratos.config().privileged_session_max_age).await?;
+    let resp = kratos
+        .delete(&format!("sessions/{session_id}"))
+        .header("cookie", forwarded_cookies().await?)
+        .send()
+        .await
+        .map_err(KratosError::from)?;
+    Ok(kratos_ok(resp).await?)
+}
+
+/// https://www.ory.sh/docs/reference/api#tag/frontend/operation/disableMyOtherSessions
+/// Returns how many sessions were revoked.
+#[tracing::instrument]
+#[server]
+pub async fn revoke_other_sessions() -> Result<i64, ServerFnError<KratosError>> {
+    let kratos = expect_context::<KratosClient>();
+    require_recent_auth(kratos.config().privileged_session_max_age).await?;
+    let resp = kratos
+        .delete("sessions")
+        .header("cookie", forwarded_cookies().await?)
+        .send()
+        .await
+        .map_err(KratosError::from)?;
+    let RevokedCount { count } = kratos_json::<RevokedCount>(resp).await?;
+    debug!("revoked {count} other sessions");
+    Ok(count)
+}
+
+#[component]
+fn SessionCard(
+    session: Session,
+    is_current: bool,
+    revoke: Action<RevokeSession, Result<(), ServerFnError<KratosError>>>,
+) -> impl IntoView {
+    let aal = session
+        .authenticator_assurance_level
+        .as_ref()
+        .and_then(enum_str)
+        .unwrap_or_default();
+    let methods = session
+        .authentication_methods
+        .iter()
+        .flatten()
+        .filter_map(|method| method.method.as_ref().and_then(enum_str))
+        .collect::<Vec<String>>()
+        .join(", ");
+    let authenticated_at = session.authenticated_at.clone().unwrap_or_default();
+    let devices = session.devices.clone().unwrap_or_default();
+    let session_id = session.id.clone();
+    view! {
+        <section class="session" class:current=is_current>
+            <h2>{if is_current { "This device" } else { "Other device" }}</h2>
+            {devices.into_iter().map(|device| view!{
+                <dl>
+                    <dt>"Browser"</dt><dd>{device.user_agent.unwrap_or_default()}</dd>
+                    <dt>"IP Address"</dt><dd>{device.ip_address.unwrap_or_default()}</dd>
+                    <dt>"Location"</dt><dd>{device.location.unwrap_or_default()}</dd>
+                </dl>
+            }).collect_view()}
+            <dl>
+                <dt>"Signed in"</dt><dd>{authenticated_at}</dd>
+                <dt>"Assurance Level"</dt><dd>{aal}</dd>
+                <dt>"Methods"</dt><dd>{methods}</dd>
+            </dl>
+            // the current session is signed out with the logout button.
+            {(!is_current).then(|| view!{
+                <button on:click=move|_|revoke.dispatch(RevokeSession{session_id: session_id.clone()})>
+                    "Revoke this session"
+                </button>
+            })}
+        </section>
+    }
+}
+
+/// A session that logged in too long ago is sent to a refresh login that comes back here, see `require_recent_auth`,
+/// and an aal1 session of an identity with a second factor to the second factor step, see `require_session`.
+fn revoke_error_html(err: ServerFnError<KratosError>) -> View {
+    match err {
+        ServerFnError::WrappedServerError(
+            KratosError::RefreshRequired {
+                redirect_browser_to: Some(url),
+            }
+            | KratosError::Aal2Required {
+                redirect_browser_to: Some(url),
+            },
+        ) => view! {<ExternalRedirect url/>}.into_view(),
+        err => view! {<p style="color:red;">{err.to_string()}</p>}.into_view(),
+    }
+}
+
+#[component]
+pub fn DevicesPage() -> impl IntoView {
+    let revoke = Action::<RevokeSession, _>::server();
+    let revoke_others = Action::<RevokeOtherSessions, _>::server();
+    // listed again after every revoke.
+    let sessions = create_resource(
+        move || (revoke.version().get(), revoke_others.version().get()),
+        |_| list_sessions(),
+    );
+    view! {
+        <h1>"Devices"</h1>
+        <Suspense fallback=||view!{"Loading Devices"}>
+            <ErrorBoundary fallback=|errors|format!("ERRORS: {:?}",errors.get()).into_view()>
+            {move || sessions.get().map(|resp| resp.map(|Sessions{current, others}|{
+                view!{
+                    <SessionCard session=current is_current=true revoke/>
+                    {(!others.is_empty()).then(|| view!{
+                        <button on:click=move|_|revoke_others.dispatch(RevokeOtherSessions{})>
+                            "Sign out everywhere else"
+                        </button>
+                    })}
+                    {others.into_iter().map(|session| view!{
+                        <SessionCard session is_current=false revoke/>
+                    }).collect_view()}
+                }
+            }))}
+            </ErrorBoundary>
+        </Suspense>
+        {move || revoke.value().get().and_then(Result::err)
+            .or_else(|| revoke_others.value().get().and_then(Result::err))
+            .map(revoke_error_html)}
+    }
+}
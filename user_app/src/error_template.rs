@@ -1,17 +1,32 @@
 use http::status::StatusCode;
 use leptos::*;
+use serde::{Deserialize, Serialize};
 use thiserror::Error;
 
-#[derive(Clone, Debug, Error)]
+#[derive(Clone, Debug, Error, Serialize, Deserialize)]
 pub enum AppError {
     #[error("Not Found")]
     NotFound,
+    /// Kratos redirected the user to our error ui with a flow error (i.e a flow was submitted twice).
+    #[error("{message} {}", .reason.clone().unwrap_or_default())]
+    FlowError {
+        code: u16,
+        message: String,
+        reason: Option<String>,
+    },
+    /// Kratos doesn't have an error with the id we were given, they expire.
+    #[error("Flow error not found")]
+    FlowErrorNotFound,
 }
 
 impl AppError {
     pub fn status_code(&self) -> StatusCode {
         match self {
             AppError::NotFound => StatusCode::NOT_FOUND,
+            AppError::FlowError { code, .. } => {
+                StatusCode::from_u16(*code).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR)
+            }
+            AppError::FlowErrorNotFound => StatusCode::NOT_FOUND,
         }
     }
 }
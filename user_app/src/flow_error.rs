@@ -0,0 +1,82 @@
+use super::*;
+
+#[cfg(feature = "ssr")]
+use crate::kratos_client::{kratos_json, KratosClient};
+use crate::kratos_error::KratosError;
+#[cfg(feature = "ssr")]
+use ory_kratos_client::models::FlowError;
+#[cfg(feature = "ssr")]
+use tracing::debug;
+
+/// The `error` of a FlowError, it's untyped in the client.
+#[cfg(feature = "ssr")]
+#[derive(Debug, Deserialize)]
+struct GenericError {
+    code: Option<u16>,
+    message: String,
+    reason: Option<String>,
+}
+
+/// https://www.ory.sh/docs/reference/api#tag/frontend/operation/getFlowError
+#[tracing::instrument]
+#[server]
+pub async fn fetch_flow_error(id: String) -> Result<AppError, ServerFnError<KratosError>> {
+    let kratos = expect_context::<KratosClient>();
+    let resp = kratos
+        .get("self-service/errors")
+        .query(&[("id", id)])
+        .send()
+        .await
+        .map_err(KratosError::from)?;
+    if resp.status().as_u16() == 404 {
+        return Ok(AppError::FlowErrorNotFound);
+    }
+    let FlowError { error, .. } = kratos_json::<FlowError>(resp).await?;
+    debug!("{:#?}", error);
+    let GenericError {
+        code,
+        message,
+        reason,
+    } = serde_json::from_value::<GenericError>(error.unwrap_or_default())
+        .map_err(KratosError::from)?;
+    Ok(AppError::FlowError {
+        code: code.unwrap_or(500),
+        message,
+        reason,
+    })
+}
+
+/// Kratos' flows.error.ui_url, Kratos sends the browser here with ?id=ERROR_ID when a flow fails.
+/// Blocking, so the status [ErrorTemplate] sets makes it into the response (the route is `SsrMode::PartiallyBlocked`).
+#[component]
+pub fn ErrorPage() -> impl IntoView {
+    let query = use_query_map();
+    let flow_error = create_blocking_resource(
+        move || query.with(|query| query.get("id").cloned()),
+        |id| async move {
+            match id {
+                Some(id) => fetch_flow_error(id).await,
+                None => Ok(AppError::FlowErrorNotFound),
+            }
+        },
+    );
+    view! {
+      <Suspense fallback=||view!{Loading Error Details}>
+        <ErrorBoundary fallback=|errors|format!("ERRORS: {:?}",errors.get()).into_view()>
+        {
+          move ||
+          flow_error.get().map(|resp|{
+                match resp {
+                    Ok(app_error) => {
+                        let mut outside_errors = Errors::default();
+                        outside_errors.insert_with_default_key(app_error);
+                        view!{<ErrorTemplate outside_errors/>}.into_view()
+                    },
+                    Err(err) => Err::<(), ServerFnError<KratosError>>(err).into_view(),
+                }
+            })
+          }
+        </ErrorBoundary>
+      </Suspense>
+    }
+}
@@ -0,0 +1,217 @@
+//! Every self service page is the same page: get a flow (a new one, or ?flow=FLOW_ID when Kratos sent us here),
+//! render it's nodes as a form, submit the form and render whatever comes back.
+//! Only the server functions differ, they're behind [KratosFlow].
+use super::*;
+
+use crate::continue_with::Destination;
+use crate::i18n::ui_text_html;
+use crate::identity_schema::{fetch_trait_inputs, TraitInputs};
+use crate::kratos_error::KratosError;
+use crate::kratos_utils::*;
+use ory_kratos_client::models::ui_node::GroupEnum;
+use ory_kratos_client::models::UiContainer;
+use serde::de::DeserializeOwned;
+use std::future::Future;
+
+/// What fetching, creating or submitting a flow gives the page.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum FlowStep<F> {
+    /// The flow to render, it's next step or the same step with validation messages on it's nodes.
+    Flow(F),
+    /// The flow is done, or Kratos wants the browser somewhere else first.
+    Continue(Destination),
+}
+
+impl<F: std::fmt::Debug> IntoView for FlowStep<F> {
+    fn into_view(self) -> View {
+        format!("{:?}", self).into_view()
+    }
+}
+
+pub type FlowResult<F> = Result<FlowStep<F>, ServerFnError<KratosError>>;
+
+/// A Kratos self service flow and the server functions that drive it.
+pub trait KratosFlow:
+    Clone + std::fmt::Debug + PartialEq + Serialize + DeserializeOwned + 'static
+{
+    /// i.e "Login", for the loading fallback.
+    const TITLE: &'static str;
+    /// Which groups (methods) to show and in which order, titled by [group_title]. Empty shows all of them, see
+    /// [group_nodes].
+    const GROUPS: &'static [GroupEnum] = &[];
+
+    fn ui(&self) -> &UiContainer;
+    fn id(&self) -> &str;
+    /// Sent along with the body for the flows whose server function decides where to go afterwards.
+    fn return_to(&self) -> Option<String> {
+        None
+    }
+    /// The identity schema of the traits this flow renders, flows without traits don't need one.
+    fn schema_id(&self) -> Option<String> {
+        None
+    }
+    /// [KratosFlow::GROUPS] unless this particular flow needs others, i.e a second factor login.
+    fn groups(&self) -> &'static [GroupEnum] {
+        Self::GROUPS
+    }
+    /// Inputs to show but not let the user change, see [lock_nodes].
+    fn locked(&self) -> &'static [&'static str] {
+        &[]
+    }
+
+    /// `query` is the page's, for flows that are made with more than a return_to (i.e login's `aal`).
+    fn init(return_to: Option<String>, query: ParamsMap) -> impl Future<Output = FlowResult<Self>>;
+    fn fetch(flow_id: String) -> impl Future<Output = FlowResult<Self>>;
+    fn submit(body: FlowBody) -> impl Future<Output = FlowResult<Self>>;
+}
+
+/// A 410 for an expired flow becomes a new flow, the replacement Kratos made for us when it did (it already has
+/// Kratos' message on it) or else a new one, with Kratos' message to show above it. Anything else is passed through.
+/// The new flow's id replaces the stale ?flow= in the url, see [keep_flow_id_in_url].
+async fn restart_expired<F: KratosFlow>(
+    resp: FlowResult<F>,
+    return_to: Option<String>,
+    query: ParamsMap,
+) -> (FlowResult<F>, Option<String>) {
+    match expired_flow(&resp) {
+        Some((Some(flow_id), _)) => (F::fetch(flow_id).await, None),
+        Some((None, message)) => (F::init(return_to, query).await, Some(message)),
+        None => (resp, None),
+    }
+}
+
+/// `groups` overrides [KratosFlow::groups] for this page.
+#[component]
+pub fn FlowPage<F: KratosFlow>(
+    #[prop(optional)] groups: Option<&'static [GroupEnum]>,
+) -> impl IntoView {
+    let submit = create_action(|body: &FlowBody| F::submit(body.clone()));
+    let return_to = return_to_query();
+    // when we hit the page initiate a flow with kratos and get back data for ui renering,
+    // unless kratos sent us here with ?flow=FLOW_ID
+    // It's blocking so the headers our server functions set (Kratos' csrf cookie, redirects) make it into the response
+    // of a direct page load, streaming would have sent them with the shell already.
+    let query = use_query_map();
+    let flow = create_blocking_resource(move || query.get(), {
+        let return_to = return_to.clone();
+        move |query| {
+            let return_to = return_to.clone();
+            async move {
+                let resp = match query.get("flow").cloned() {
+                    Some(flow_id) => F::fetch(flow_id).await,
+                    None => F::init(return_to.clone(), query.clone()).await,
+                };
+                // i.e reloading the page, or an emailed link, after the flow's lifespan.
+                restart_expired(resp, return_to, query).await
+            }
+        }
+    });
+    // Is none if user hasn't submitted data.
+    let submit_resp = create_rw_signal(None::<(FlowResult<F>, Option<String>)>);
+    // this is the body of our form, we don't know what the inputs are so it's a stand in for some
+    // json map of unknown argument length with type of string.
+    let body = create_rw_signal(HashMap::new());
+    create_effect(move |_| {
+        if let Some(resp) = submit.value().get() {
+            if expired_flow(&resp).is_some() {
+                // keep what the user typed (but not their secrets).
+                retain_non_secret_fields(body);
+                let return_to = return_to.clone();
+                spawn_local(async move {
+                    let restarted = restart_expired(resp, return_to, query.get_untracked()).await;
+                    submit_resp.set(Some(restarted));
+                });
+            } else {
+                // a new step means new inputs, we don't want to resend the last step's values.
+                // Kratos puts whatever should stay (i.e the identifier) back on the nodes.
+                body.set(HashMap::new());
+                submit_resp.set(Some((resp, None)))
+            }
+        }
+    });
+    // Merge our resource and our action results into a single signal.
+    // if the user hasn't submitted yet we'll render the initial flow,
+    // otherwise the updated flow (including error messages etc).
+    let flow_and_message = Signal::derive(move || {
+        if let Some(resp) = submit_resp.get() {
+            Some(resp)
+        } else {
+            flow.get()
+        }
+    });
+    let flow = Signal::derive(move || flow_and_message.get().map(|(resp, _)| resp));
+    // Kratos' message when we had to restart an expired flow ourselves.
+    let expired_message =
+        Signal::derive(move || flow_and_message.get().and_then(|(_, message)| message));
+    // the ?flow= follows the flow, so reloading while i.e waiting for an emailed code comes back to the same step
+    // instead of starting over.
+    create_effect(move |_| {
+        flow.with(|resp| {
+            if let Some(Ok(FlowStep::Flow(flow))) = resp {
+                keep_flow_id_in_url(flow.id());
+            }
+        })
+    });
+    let schema_id = Signal::derive(move || {
+        flow.with(|resp| match resp {
+            Some(Ok(FlowStep::Flow(flow))) => flow.schema_id(),
+            _ => None,
+        })
+    });
+    // without it enums and arrays are plain inputs, which is still usable.
+    let trait_inputs = create_resource(
+        move || schema_id.get(),
+        |schema_id| async move {
+            match schema_id {
+                Some(schema_id) => fetch_trait_inputs(schema_id).await.unwrap_or_else(|err| {
+                    leptos::logging::error!("Failed to fetch the identity schema: {err}");
+                    TraitInputs::default()
+                }),
+                None => TraitInputs::default(),
+            }
+        },
+    );
+    view! {
+      <Suspense fallback=||view!{"Loading "{F::TITLE}" Details"}>
+        <ErrorBoundary fallback=|errors|format!("ERRORS: {:?}",errors.get()).into_view()>
+        {
+          move ||
+          flow.get().map(|resp|{
+                match resp {
+                    Ok(FlowStep::Flow(flow)) => {
+                        let UiContainer{nodes,messages,action,method,..} = flow.ui().clone();
+                        let nodes = lock_nodes(nodes, flow.locked());
+                        // tells our intermediary server function where to pass on the data to, and where to go after.
+                        body.update(|map|{
+                            _=map.insert(String::from("action"),action.clone());
+                            if let Some(return_to) = flow.return_to() {
+                                _=map.insert(String::from("return_to"),return_to);
+                            }
+                        });
+                        let trait_inputs = trait_inputs.get().unwrap_or_default();
+                        let groups = groups.unwrap_or_else(|| flow.groups());
+                        let forms = node_groups_html(nodes, &action, &method, groups, body, &trait_inputs, move |body| submit.dispatch(body));
+                        view!{
+                            {move || expired_message.get().map(|text|view!{<p>{text}</p>})}
+                            {forms}
+                            // node_html renders messages for each node and these are the messages attached to the entire flow.
+                            {messages.map(|messages|{
+                                view!{
+                                    <For
+                                        each=move || messages.clone().into_iter()
+                                        key=|text| text.id
+                                        children=ui_text_html
+                                    />
+                                }
+                            }).unwrap_or_default()}
+                        }.into_view()
+                    },
+                    Ok(FlowStep::Continue(destination)) => destination.into_view(),
+                    err => err.into_view(),
+                }
+            })
+          }
+        </ErrorBoundary>
+      </Suspense>
+    }
+}
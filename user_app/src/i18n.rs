@@ -0,0 +1,196 @@
+//! Kratos' messages (`UiText`) have stable numeric ids, https://www.ory.sh/docs/kratos/concepts/ui-messages
+//! so we translate by id and fill in the message's `context` instead of matching on Kratos' English text.
+use super::*;
+
+use ory_kratos_client::models::ui_text::TypeEnum;
+use ory_kratos_client::models::UiText;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Locale {
+    /// Kratos' own text.
+    #[default]
+    En,
+    De,
+}
+
+impl Locale {
+    pub fn code(&self) -> &'static str {
+        match self {
+            Locale::En => "en",
+            Locale::De => "de",
+        }
+    }
+
+    /// i.e `de-CH` is German.
+    pub fn from_code(code: &str) -> Option<Self> {
+        match code.split('-').next()?.trim().to_lowercase().as_str() {
+            "en" => Some(Locale::En),
+            "de" => Some(Locale::De),
+            _ => None,
+        }
+    }
+
+    /// i.e `de-CH, de;q=0.9, en;q=0.8`, the most preferred language we have a catalogue for.
+    pub fn from_accept_language(header: &str) -> Self {
+        let mut languages = header
+            .split(',')
+            .filter_map(|language| {
+                let mut parts = language.split(";q=");
+                let code = parts.next()?.trim();
+                let q = parts
+                    .next()
+                    .and_then(|q| q.trim().parse::<f32>().ok())
+                    .unwrap_or(1.0);
+                Some((Locale::from_code(code)?, q))
+            })
+            .collect::<Vec<(Locale, f32)>>();
+        // stable, so equal q's keep the browser's order.
+        languages.sort_by(|(_, a), (_, b)| b.total_cmp(a));
+        languages
+            .first()
+            .map(|(locale, _)| *locale)
+            .unwrap_or_default()
+    }
+
+    /// The template for a message id, `{name}` is filled in from the message's context.
+    fn template(&self, id: i64) -> Option<&'static str> {
+        match self {
+            Locale::En => None,
+            Locale::De => match id {
+                1010001 => Some("Anmelden"),
+                1010002 => Some("Anmelden mit {provider}"),
+                1010003 => Some("Bitte bestätige diese Aktion, indem du dich erneut anmeldest."),
+                1010004 => Some("Bitte schließe die zweite Authentifizierung ab."),
+                1010005 => Some("Bestätigen"),
+                1010006 => Some("Authentifizierungscode"),
+                1010007 => Some("Backup-Wiederherstellungscode"),
+                1010014 => Some("Eine E-Mail mit einem Code wurde an die angegebene Adresse gesendet."),
+                1010015 => Some("Mit Code anmelden"),
+                1040001 => Some("Registrieren"),
+                1040002 => Some("Registrieren mit {provider}"),
+                1040005 => Some("Eine E-Mail mit einem Code wurde an die angegebene Adresse gesendet."),
+                1040006 => Some("Mit Code registrieren"),
+                1050001 => Some("Deine Änderungen wurden gespeichert!"),
+                1070001 => Some("Passwort"),
+                1070002 => Some("{title}"),
+                1070003 => Some("Speichern"),
+                1070004 => Some("Kennung"),
+                1070005 => Some("Absenden"),
+                1070007 => Some("E-Mail"),
+                1070008 => Some("Code erneut senden"),
+                1080001 => Some("Eine E-Mail mit einem Bestätigungslink wurde an die angegebene Adresse gesendet."),
+                1080002 => Some("Deine E-Mail-Adresse wurde erfolgreich bestätigt."),
+                4000002 => Some("Die Eigenschaft {property} fehlt."),
+                4000006 => Some("Die Zugangsdaten sind ungültig, bitte prüfe Passwort, Benutzername, E-Mail-Adresse oder Telefonnummer."),
+                4000007 => Some("Ein Konto mit dieser Kennung (E-Mail, Telefon, Benutzername, ...) existiert bereits."),
+                _ => None,
+            },
+        }
+    }
+}
+
+/// The page's locale, picked once per page load by [provide_locale].
+pub fn use_locale() -> Locale {
+    use_context::<Locale>().unwrap_or_default()
+}
+
+/// On the server from the request's `Accept-Language`, in the browser from the `<html lang>` the server rendered
+/// so hydration renders the same text.
+pub fn provide_locale() -> Locale {
+    #[cfg(feature = "ssr")]
+    let locale = use_context::<http::request::Parts>()
+        .and_then(|parts| {
+            parts
+                .headers
+                .get("accept-language")
+                .and_then(|header| header.to_str().ok())
+                .map(Locale::from_accept_language)
+        })
+        .unwrap_or_default();
+    #[cfg(not(feature = "ssr"))]
+    let locale = document()
+        .document_element()
+        .and_then(|html| html.get_attribute("lang"))
+        .and_then(|lang| Locale::from_code(&lang))
+        .unwrap_or_default();
+    provide_context(locale);
+    locale
+}
+
+/// Our text for the message, or Kratos' when we don't have a translation.
+pub fn translate(text: &UiText, locale: Locale) -> String {
+    let Some(template) = locale.template(text.id) else {
+        return text.text.clone();
+    };
+    let mut translated = template.to_string();
+    if let Some(serde_json::Value::Object(context)) = &text.context {
+        for (key, value) in context {
+            let value = match value {
+                serde_json::Value::String(value) => value.clone(),
+                value => value.to_string(),
+            };
+            translated = translated.replace(&format!("{{{key}}}"), &value);
+        }
+    }
+    translated
+}
+
+/// A message styled by it's type, `data-message-id` is Kratos' message id (the same one can show up more than once).
+pub fn ui_text_html(text: UiText) -> impl IntoView {
+    let style = match text._type {
+        TypeEnum::Error => "color:red;",
+        TypeEnum::Success => "color:green;",
+        TypeEnum::Info => "",
+    };
+    let class = match text._type {
+        TypeEnum::Error => "message error",
+        TypeEnum::Success => "message success",
+        TypeEnum::Info => "message info",
+    };
+    view! {<p data-message-id=text.id class=class style=style>{translate(&text, use_locale())}</p>}
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn most_preferred_language_we_have() {
+        assert_eq!(
+            Locale::from_accept_language("de-CH, de;q=0.9, en;q=0.8"),
+            Locale::De
+        );
+        assert_eq!(
+            Locale::from_accept_language("fr-FR, en;q=0.5, de;q=0.6"),
+            Locale::De
+        );
+        assert_eq!(Locale::from_accept_language("en;q=0.5, de"), Locale::De);
+        assert_eq!(Locale::from_accept_language("EN-us, de;q=0.9"), Locale::En);
+    }
+
+    #[test]
+    fn equal_preferences_keep_the_browsers_order() {
+        assert_eq!(Locale::from_accept_language("de, en"), Locale::De);
+        assert_eq!(Locale::from_accept_language("en, de"), Locale::En);
+    }
+
+    #[test]
+    fn falls_back_to_kratos_own_text() {
+        assert_eq!(Locale::from_accept_language(""), Locale::En);
+        assert_eq!(Locale::from_accept_language("*"), Locale::En);
+        assert_eq!(Locale::from_accept_language("fr-FR, fr;q=0.9"), Locale::En);
+    }
+
+    #[test]
+    fn fills_in_the_context() {
+        let text = serde_json::from_value::<UiText>(serde_json::json!({
+            "id": 1010002,
+            "text": "Sign in with github",
+            "type": "info",
+            "context": {"provider": "github"},
+        }))
+        .unwrap();
+        assert_eq!(translate(&text, Locale::De), "Anmelden mit github");
+        assert_eq!(translate(&text, Locale::En), "Sign in with github");
+    }
+}
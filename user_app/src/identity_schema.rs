@@ -0,0 +1,167 @@
+//! Flow nodes only tell us an input's html type, the identity schema tells us a trait is an `enum` (a select)
+//! or an `array` (many values), so pages that render traits look it up.
+use super::*;
+
+#[cfg(feature = "ssr")]
+use crate::kratos_client::{kratos_json, KratosClient};
+use crate::kratos_error::KratosError;
+
+/// identity.default_schema_id in kratos.yaml
+pub const DEFAULT_SCHEMA_ID: &str = "default";
+
+/// How to render a trait beyond what it's node says.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct TraitInput {
+    /// The schema's `enum` (or it's items' `enum` for arrays), empty means any value.
+    pub options: Vec<String>,
+    /// An `array` trait, it's value in the form body is a json array.
+    pub multiple: bool,
+}
+
+/// Keyed by node name, i.e `traits.name.first`
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct TraitInputs(pub HashMap<String, TraitInput>);
+
+impl TraitInputs {
+    pub fn get(&self, name: &str) -> Option<&TraitInput> {
+        self.0.get(name)
+    }
+
+    /// Walks `properties.traits` of a json schema, only traits that need more than a plain input are kept.
+    pub fn from_schema(schema: &serde_json::Value) -> Self {
+        let mut inputs = HashMap::new();
+        if let Some(traits) = schema.pointer("/properties/traits") {
+            collect_traits(traits, "traits", &mut inputs);
+        }
+        Self(inputs)
+    }
+}
+
+fn collect_traits(
+    schema: &serde_json::Value,
+    path: &str,
+    inputs: &mut HashMap<String, TraitInput>,
+) {
+    let options = |schema: &serde_json::Value| {
+        schema
+            .get("enum")
+            .and_then(|options| options.as_array())
+            .map(|options| {
+                options
+                    .iter()
+                    .map(|option| match option {
+                        serde_json::Value::String(option) => option.clone(),
+                        option => option.to_string(),
+                    })
+                    .collect::<Vec<String>>()
+            })
+    };
+    match schema.get("type").and_then(|_type| _type.as_str()) {
+        Some("object") => {
+            if let Some(properties) = schema.get("properties").and_then(|p| p.as_object()) {
+                for (name, property) in properties {
+                    collect_traits(property, &format!("{path}.{name}"), inputs);
+                }
+            }
+        }
+        Some("array") => {
+            let options = schema.get("items").and_then(options).unwrap_or_default();
+            _ = inputs.insert(
+                path.to_string(),
+                TraitInput {
+                    options,
+                    multiple: true,
+                },
+            );
+        }
+        _ => {
+            if let Some(options) = options(schema) {
+                _ = inputs.insert(
+                    path.to_string(),
+                    TraitInput {
+                        options,
+                        multiple: false,
+                    },
+                );
+            }
+        }
+    }
+}
+
+/// https://www.ory.sh/docs/reference/api#tag/identity/operation/getIdentitySchema
+#[tracing::instrument]
+#[server]
+pub async fn fetch_trait_inputs(
+    schema_id: String,
+) -> Result<TraitInputs, ServerFnError<KratosError>> {
+    let kratos = expect_context::<KratosClient>();
+    let resp = kratos
+        .get(&format!("schemas/{schema_id}"))
+        .send()
+        .await
+        .map_err(KratosError::from)?;
+    let schema = kratos_json::<serde_json::Value>(resp).await?;
+    Ok(TraitInputs::from_schema(&schema))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_traits_that_need_more_than_a_plain_input() {
+        let schema = serde_json::json!({
+            "type": "object",
+            "properties": {
+                "traits": {
+                    "type": "object",
+                    "properties": {
+                        "email": {"type": "string", "format": "email"},
+                        "name": {
+                            "type": "object",
+                            "properties": {
+                                "first": {"type": "string"},
+                                "title": {"type": "string", "enum": ["Dr", "Ms", "Mr"]},
+                            },
+                        },
+                        "shoe_size": {"type": "number", "enum": [42, 43]},
+                        "languages": {
+                            "type": "array",
+                            "items": {"type": "string", "enum": ["en", "de"]},
+                        },
+                        "tags": {"type": "array", "items": {"type": "string"}},
+                    },
+                    "required": ["email"],
+                },
+            },
+        });
+        let option = |options: &[&str], multiple: bool| TraitInput {
+            options: options.iter().map(|option| option.to_string()).collect(),
+            multiple,
+        };
+        assert_eq!(
+            TraitInputs::from_schema(&schema),
+            TraitInputs(HashMap::from([
+                (
+                    String::from("traits.name.title"),
+                    option(&["Dr", "Ms", "Mr"], false)
+                ),
+                (
+                    String::from("traits.shoe_size"),
+                    option(&["42", "43"], false)
+                ),
+                (
+                    String::from("traits.languages"),
+                    option(&["en", "de"], true)
+                ),
+                (String::from("traits.tags"), option(&[], true)),
+            ]))
+        );
+    }
+
+    #[test]
+    fn a_schema_without_traits_has_no_inputs() {
+        let schema = serde_json::json!({"type": "object", "properties": {}});
+        assert_eq!(TraitInputs::from_schema(&schema), TraitInputs::default());
+    }
+}
@@ -0,0 +1,293 @@
+//! One pooled client for talking to Kratos' public api on behalf of the browser.
+//! It forwards the browser's Kratos cookies in and Kratos' `set-cookie`s back out through `ResponseOptions`,
+//! so it must be called from inside a server function (or a component rendering on the server).
+use crate::config::AppConfig;
+use crate::kratos_error::KratosError;
+use crate::kratos_utils::FlowBody;
+use leptos::expect_context;
+use ory_kratos_client::models::{
+    LoginFlow, RecoveryFlow, RegistrationFlow, SettingsFlow, VerificationFlow,
+};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use tracing::debug;
+
+/// A self service flow, `NAME` is it's segment in Kratos' paths i.e /self-service/{NAME}/browser
+pub trait FlowKind: DeserializeOwned {
+    const NAME: &'static str;
+}
+impl FlowKind for LoginFlow {
+    const NAME: &'static str = "login";
+}
+impl FlowKind for RegistrationFlow {
+    const NAME: &'static str = "registration";
+}
+impl FlowKind for VerificationFlow {
+    const NAME: &'static str = "verification";
+}
+impl FlowKind for RecoveryFlow {
+    const NAME: &'static str = "recovery";
+}
+impl FlowKind for SettingsFlow {
+    const NAME: &'static str = "settings";
+}
+
+/// What Kratos said about a flow, anything else is a [KratosError].
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum FlowResponse<F> {
+    /// The next step of the flow, or the same step with validation messages on it's nodes.
+    Flow(F),
+    /// The flow is done and Kratos answered with something other than a flow, i.e the new session on login.
+    Success(serde_json::Value),
+}
+
+impl<F> FlowResponse<F> {
+    /// For when anything but a flow is unexpected, i.e creating or fetching a flow.
+    pub fn into_flow(self) -> Result<F, KratosError> {
+        match self {
+            FlowResponse::Flow(flow) => Ok(flow),
+            FlowResponse::Success(value) => Err(KratosError::Unexpected(format!(
+                "Expecting a flow but got: {value}"
+            ))),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct KratosClient {
+    client: reqwest::Client,
+    config: AppConfig,
+}
+
+impl KratosClient {
+    pub fn new(config: AppConfig) -> Result<Self, reqwest::Error> {
+        let client = reqwest::ClientBuilder::new()
+            // Kratos' redirects are meant for the browser, not us.
+            .redirect(reqwest::redirect::Policy::none())
+            .build()?;
+        Ok(Self { client, config })
+    }
+
+    pub fn config(&self) -> &AppConfig {
+        &self.config
+    }
+
+    /// https://www.ory.sh/docs/kratos/self-service#browser-flows-for-client-side-apps-single-page-apps-reactjs-angular-nextjs-
+    /// Asking for json means Kratos answers with the flow instead of redirecting to our ui_url.
+    /// `return_to` is where the browser ends up once the flow is done, it has to be an allowed return url.
+    pub async fn create_browser_flow<F: FlowKind>(
+        &self,
+        return_to: Option<String>,
+        query: &[(&str, &str)],
+    ) -> Result<FlowResponse<F>, KratosError> {
+        let mut req = self
+            .client
+            .get(
+                self.config
+                    .kratos_public(&format!("self-service/{}/browser", F::NAME)),
+            )
+            .query(query);
+        if let Some(return_to) = return_to {
+            req = req.query(&[("return_to", self.checked_return_to(&return_to)?.as_str())]);
+        }
+        let resp = req
+            .header("accept", "application/json")
+            .header("cookie", forwarded_cookies().await?)
+            .send()
+            .await?;
+        handle_response(resp).await
+    }
+
+    pub async fn get_flow<F: FlowKind>(
+        &self,
+        flow_id: &str,
+    ) -> Result<FlowResponse<F>, KratosError> {
+        let resp = self
+            .client
+            .get(
+                self.config
+                    .kratos_public(&format!("self-service/{}/flows", F::NAME)),
+            )
+            .query(&[("id", flow_id)])
+            .header("accept", "application/json")
+            .header("cookie", forwarded_cookies().await?)
+            .send()
+            .await?;
+        handle_response(resp).await
+    }
+
+    /// Posts the body to the flow, `action` is it's ui.action as the browser sent it back. Only the flow id is taken
+    /// from it, the url is always Kratos' so the browser can't have us post it's cookies anywhere else.
+    pub async fn submit_flow<F: FlowKind>(
+        &self,
+        action: &str,
+        body: &FlowBody,
+    ) -> Result<FlowResponse<F>, KratosError> {
+        let flow_id = action_flow_id(action)?;
+        let mut req = self
+            .client
+            .post(
+                self.config
+                    .kratos_public(&format!("self-service/{}", F::NAME)),
+            )
+            .query(&[("flow", flow_id.as_str())])
+            .header("content-type", "application/json")
+            .header("accept", "application/json")
+            .header("cookie", forwarded_cookies().await?);
+        if let Some(csrf_token) = csrf_token().await? {
+            req = req.header("x-csrf-token", csrf_token);
+        }
+        let resp = req.body(serde_json::to_string(body)?).send().await?;
+        handle_response(resp).await
+    }
+
+    /// Rejects anything outside selfservice.allowed_return_urls, so we can't be used as an open redirect.
+    pub fn checked_return_to(&self, return_to: &str) -> Result<url::Url, KratosError> {
+        self.config
+            .allowed_return_to(return_to)
+            .ok_or_else(|| KratosError::Generic {
+                code: 400,
+                id: Some(String::from("self_service_flow_return_to_forbidden")),
+                message: String::from("The request was malformed or contained invalid parameters"),
+                reason: Some(format!(
+                    "Requested return_to URL \"{return_to}\" is not allowed."
+                )),
+            })
+    }
+
+    /// For the endpoints that aren't flows (whoami, logout, errors), the caller adds whichever cookies it needs.
+    pub fn get(&self, path: &str) -> reqwest::RequestBuilder {
+        self.client
+            .get(self.config.kratos_public(path))
+            .header("accept", "application/json")
+    }
+
+    /// Like [KratosClient::get], i.e revoking a session.
+    pub fn delete(&self, path: &str) -> reqwest::RequestBuilder {
+        self.client
+            .delete(self.config.kratos_public(path))
+            .header("accept", "application/json")
+    }
+}
+
+/// The flow id in a flow's ui.action, i.e http://127.0.0.1:4433/self-service/login?flow=<id>
+fn action_flow_id(action: &str) -> Result<String, KratosError> {
+    url::Url::parse(action)
+        .ok()
+        .and_then(|url| {
+            url.query_pairs()
+                .find(|(key, _)| key == "flow")
+                .map(|(_, id)| id.into_owned())
+        })
+        .ok_or_else(|| {
+            KratosError::Unexpected(format!("Can't find the flow id in action: {action}"))
+        })
+}
+
+async fn handle_response<F: FlowKind>(
+    resp: reqwest::Response,
+) -> Result<FlowResponse<F>, KratosError> {
+    debug!("{:#?}", resp);
+    forward_set_cookies(&resp)?;
+    let status = resp.status().as_u16();
+    let text = resp.text().await?;
+    if matches!(status, 200 | 400) {
+        let value = serde_json::from_str::<serde_json::Value>(&text)?;
+        // 400 is the flow with validation messages attached to the nodes.
+        if value.get("ui").is_some() {
+            return Ok(FlowResponse::Flow(serde_json::from_value::<F>(value)?));
+        } else if status == 200 {
+            return Ok(FlowResponse::Success(value));
+        }
+    }
+    let err = KratosError::from_response(status, &text);
+    debug!("{:#?}", err);
+    Err(err)
+}
+
+/// Kratos' answer to anything that isn't a flow (i.e logging out or a flow error): the json of a 2xx, otherwise the
+/// [KratosError] in it's body.
+pub async fn kratos_json<T: serde::de::DeserializeOwned>(
+    resp: reqwest::Response,
+) -> Result<T, KratosError> {
+    let text = kratos_text(resp).await?;
+    Ok(serde_json::from_str::<T>(&text)?)
+}
+
+/// Like [kratos_json] for the answers without a body, i.e 204 after logging out.
+pub async fn kratos_ok(resp: reqwest::Response) -> Result<(), KratosError> {
+    kratos_text(resp).await.map(|_| ())
+}
+
+async fn kratos_text(resp: reqwest::Response) -> Result<String, KratosError> {
+    debug!("{:#?}", resp);
+    let status = resp.status();
+    let text = resp.text().await?;
+    if !status.is_success() {
+        return Err(KratosError::from_response(status.as_u16(), &text));
+    }
+    Ok(text)
+}
+
+/// The browser's Kratos cookies, the csrf cookie (named csrf_token + a lot of nonsense i.e csrf_token8912490183...),
+/// the session and the continuity cookie Kratos uses to pick a flow back up after the browser comes back from an
+/// OIDC provider.
+pub async fn forwarded_cookies() -> Result<String, KratosError> {
+    let cookie_jar = leptos_axum::extract::<axum_extra::extract::CookieJar>().await?;
+    Ok(cookie_jar
+        .iter()
+        .filter(|cookie| {
+            cookie.name().contains("csrf_token")
+                || cookie.name() == "ory_kratos_session"
+                || cookie.name() == "ory_kratos_continuity"
+        })
+        .map(|cookie| format!("{}={}", cookie.name(), cookie.value()))
+        .collect::<Vec<String>>()
+        .join("; "))
+}
+
+async fn csrf_token() -> Result<Option<String>, KratosError> {
+    let cookie_jar = leptos_axum::extract::<axum_extra::extract::CookieJar>().await?;
+    Ok(cookie_jar
+        .iter()
+        .find(|cookie| cookie.name().contains("csrf_token"))
+        .map(|cookie| cookie.value().to_string()))
+}
+
+/// Kratos' cookies have to be set on our origin, and none of this should be cached.
+pub fn forward_set_cookies(resp: &reqwest::Response) -> Result<(), KratosError> {
+    let opts = expect_context::<leptos_axum::ResponseOptions>();
+    opts.insert_header(
+        axum::http::HeaderName::from_static("cache-control"),
+        axum::http::HeaderValue::from_static("private, no-cache, no-store, must-revalidate"),
+    );
+    for value in resp.headers().get_all("set-cookie").iter() {
+        opts.append_header(
+            axum::http::HeaderName::from_static("set-cookie"),
+            axum::http::HeaderValue::from_str(value.to_str()?)?,
+        );
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_the_flow_id_comes_from_the_action() {
+        assert_eq!(
+            action_flow_id("http://127.0.0.1:4433/self-service/login?flow=abc").unwrap(),
+            "abc"
+        );
+        assert_eq!(
+            action_flow_id("http://evil.example.com/steal?flow=abc&other=1").unwrap(),
+            "abc"
+        );
+    }
+
+    #[test]
+    fn an_action_without_a_flow_id_is_an_error() {
+        assert!(action_flow_id("http://127.0.0.1:4433/self-service/login").is_err());
+        assert!(action_flow_id("/self-service/login?flow=abc").is_err());
+    }
+}
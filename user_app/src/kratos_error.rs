@@ -0,0 +1,255 @@
+//! What went wrong talking to Kratos, parsed from it's `ErrorGeneric` bodies so pages can react to each case.
+//! Server functions return it as `ServerFnError<KratosError>`, which crosses the server function boundary through
+//! it's `Display`/`FromStr` (json) implementations.
+use serde::{Deserialize, Serialize};
+use std::{fmt::Display, str::FromStr};
+
+/// A 400 carrying an updated flow isn't an error (it's the flow with validation messages on it's nodes),
+/// so it's returned as the flow and never shows up here.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum KratosError {
+    /// 410 self_service_flow_expired, Kratos sometimes made a replacement flow for us already.
+    FlowExpired {
+        use_flow_id: Option<String>,
+        message: String,
+    },
+    /// 403 security_csrf_violation, usually the csrf cookie didn't make it to Kratos.
+    CsrfViolation { message: String },
+    /// 400 session_already_available, i.e logging in while logged in.
+    SessionAlreadyAvailable { message: String },
+    /// 403 session_aal2_required, the session needs a second factor first.
+    Aal2Required { redirect_browser_to: Option<String> },
+    /// 403 session_refresh_required, the session is older than privileged_session_max_age.
+    RefreshRequired { redirect_browser_to: Option<String> },
+    /// 422 browser_location_change_required, i.e settings after a recovery code or an OIDC provider's login page.
+    BrowserLocationChangeRequired { redirect_browser_to: String },
+    /// 401 session_inactive, there is no (valid) session.
+    SessionInactive,
+    /// Any other error Kratos told us about.
+    Generic {
+        code: u16,
+        id: Option<String>,
+        message: String,
+        reason: Option<String>,
+    },
+    /// We couldn't talk to Kratos, or didn't understand what it said.
+    Unexpected(String),
+}
+
+/// https://www.ory.sh/docs/reference/api#tag/frontend/operation/getFlowError (ErrorGeneric and friends)
+#[derive(Debug, Deserialize)]
+struct ErrorBody {
+    error: GenericError,
+    redirect_browser_to: Option<String>,
+    use_flow_id: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct GenericError {
+    id: Option<String>,
+    message: String,
+    reason: Option<String>,
+    details: Option<serde_json::Value>,
+}
+
+impl KratosError {
+    /// Parses the body of a non 2xx response from Kratos.
+    pub fn from_response(status: u16, body: &str) -> Self {
+        let Ok(ErrorBody {
+            error:
+                GenericError {
+                    id,
+                    message,
+                    reason,
+                    details,
+                },
+            redirect_browser_to,
+            use_flow_id,
+        }) = serde_json::from_str::<ErrorBody>(body)
+        else {
+            return KratosError::Generic {
+                code: status,
+                id: None,
+                message: body.to_string(),
+                reason: None,
+            };
+        };
+        match id.as_deref() {
+            Some("self_service_flow_expired") => KratosError::FlowExpired {
+                // older versions of Kratos only put it in the details.
+                use_flow_id: use_flow_id.or(details
+                    .as_ref()
+                    .and_then(|details| details.get("use_flow_id"))
+                    .and_then(|id| id.as_str())
+                    .map(String::from)),
+                // "The self-service flow expired 0.61 minutes ago, initialize a new one."
+                message: reason.unwrap_or(message),
+            },
+            Some("security_csrf_violation") => KratosError::CsrfViolation { message },
+            Some("session_already_available") => KratosError::SessionAlreadyAvailable { message },
+            Some("session_aal2_required") => KratosError::Aal2Required {
+                redirect_browser_to,
+            },
+            Some("session_refresh_required") => KratosError::RefreshRequired {
+                redirect_browser_to,
+            },
+            Some("session_inactive") => KratosError::SessionInactive,
+            _ => match redirect_browser_to {
+                Some(redirect_browser_to) if status == 422 => {
+                    KratosError::BrowserLocationChangeRequired {
+                        redirect_browser_to,
+                    }
+                }
+                _ => KratosError::Generic {
+                    code: status,
+                    id,
+                    message,
+                    reason,
+                },
+            },
+        }
+    }
+
+    pub fn status_code(&self) -> u16 {
+        match self {
+            KratosError::FlowExpired { .. } => 410,
+            KratosError::CsrfViolation { .. } => 403,
+            KratosError::SessionAlreadyAvailable { .. } => 400,
+            KratosError::Aal2Required { .. } => 403,
+            KratosError::RefreshRequired { .. } => 403,
+            KratosError::BrowserLocationChangeRequired { .. } => 422,
+            KratosError::SessionInactive => 401,
+            KratosError::Generic { code, .. } => *code,
+            KratosError::Unexpected(_) => 500,
+        }
+    }
+}
+
+// KratosError deliberately doesn't implement std::error::Error, so that anything that does
+// (reqwest, serde_json, ServerFnError...) can be turned into one with `?`.
+impl<E: std::error::Error> From<E> for KratosError {
+    fn from(err: E) -> Self {
+        KratosError::Unexpected(err.to_string())
+    }
+}
+
+// server_fn sends custom errors as their Display and reads them back with FromStr.
+impl Display for KratosError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match serde_json::to_string(self) {
+            Ok(json) => write!(f, "{json}"),
+            Err(_) => write!(f, "{self:?}"),
+        }
+    }
+}
+
+impl FromStr for KratosError {
+    type Err = serde_json::Error;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        serde_json::from_str(s)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn expired_flow_with_use_flow_id_in_details() {
+        let body = r#"{
+            "error": {
+                "id": "self_service_flow_expired",
+                "code": 410,
+                "status": "Gone",
+                "reason": "The self-service flow expired 0.61 minutes ago, initialize a new one.",
+                "message": "self-service flow expired",
+                "details": {
+                    "since": 36600000000,
+                    "use_flow_id": "4a3b0a1e-5c2d-4e8f-9a7b-6c5d4e3f2a1b"
+                }
+            }
+        }"#;
+        assert_eq!(
+            KratosError::from_response(410, body),
+            KratosError::FlowExpired {
+                use_flow_id: Some(String::from("4a3b0a1e-5c2d-4e8f-9a7b-6c5d4e3f2a1b")),
+                message: String::from(
+                    "The self-service flow expired 0.61 minutes ago, initialize a new one."
+                ),
+            }
+        );
+    }
+
+    #[test]
+    fn expired_flow_prefers_the_top_level_use_flow_id() {
+        let body = r#"{
+            "error": {
+                "id": "self_service_flow_expired",
+                "message": "self-service flow expired",
+                "details": {"use_flow_id": "old"}
+            },
+            "use_flow_id": "new"
+        }"#;
+        assert_eq!(
+            KratosError::from_response(410, body),
+            KratosError::FlowExpired {
+                use_flow_id: Some(String::from("new")),
+                message: String::from("self-service flow expired"),
+            }
+        );
+    }
+
+    #[test]
+    fn browser_location_change_required() {
+        let body = r#"{
+            "error": {"id": "browser_location_change_required", "code": 422, "message": "browser location change required"},
+            "redirect_browser_to": "http://127.0.0.1:4433/self-service/methods/oidc/auth/1"
+        }"#;
+        assert_eq!(
+            KratosError::from_response(422, body),
+            KratosError::BrowserLocationChangeRequired {
+                redirect_browser_to: String::from(
+                    "http://127.0.0.1:4433/self-service/methods/oidc/auth/1"
+                ),
+            }
+        );
+    }
+
+    #[test]
+    fn refresh_required() {
+        let body = r#"{
+            "error": {"id": "session_refresh_required", "code": 403, "message": "The session is too old"},
+            "redirect_browser_to": "http://127.0.0.1:4433/self-service/login/browser?refresh=true"
+        }"#;
+        assert_eq!(
+            KratosError::from_response(403, body),
+            KratosError::RefreshRequired {
+                redirect_browser_to: Some(String::from(
+                    "http://127.0.0.1:4433/self-service/login/browser?refresh=true"
+                )),
+            }
+        );
+    }
+
+    #[test]
+    fn anything_else_is_generic() {
+        assert_eq!(
+            KratosError::from_response(502, "Bad Gateway"),
+            KratosError::Generic {
+                code: 502,
+                id: None,
+                message: String::from("Bad Gateway"),
+                reason: None,
+            }
+        );
+    }
+
+    #[test]
+    fn crosses_the_server_function_boundary() {
+        let err = KratosError::FlowExpired {
+            use_flow_id: None,
+            message: String::from("expired"),
+        };
+        assert_eq!(err.to_string().parse::<KratosError>().unwrap(), err);
+    }
+}
@@ -1,20 +1,72 @@
 use super::*;
+use crate::i18n::{translate, ui_text_html, use_locale};
+use crate::identity_schema::{TraitInput, TraitInputs};
+use crate::kratos_error::KratosError;
+use leptos::nonce::use_nonce;
+use ory_kratos_client::models::ui_node::GroupEnum;
 use ory_kratos_client::models::ui_node_attributes::UiNodeAttributes;
 use ory_kratos_client::models::ui_node_attributes::UiNodeAttributesTypeEnum;
-use ory_kratos_client::models::UiNode;
-use ory_kratos_client::models::UiText;
+use ory_kratos_client::models::ui_text;
+use ory_kratos_client::models::{UiNode, UiText};
 
-type FlowId = String;
-#[derive(Debug, PartialEq, Clone)]
-pub enum FlowStages {
-    HandleFlow(FlowId),
-    Init,
+/// ?return_to=URL on our page, passed on to Kratos when we start a flow so the user ends up back there afterwards.
+/// Must be called where the router is available, i.e at the top of a page component.
+pub fn return_to_query() -> Option<String> {
+    use_query_map().get_untracked().get("return_to").cloned()
+}
+
+/// Inputs we never carry over into a new flow.
+const SECRET_FIELDS: [&str; 6] = [
+    "password",
+    "code",
+    "totp_code",
+    "lookup_secret",
+    "csrf_token",
+    "method",
+];
+
+/// If Kratos says the flow expired (410) returns the id of the replacement flow Kratos made for us (if it did)
+/// and Kratos' message about it.
+pub fn expired_flow<T>(
+    resp: &Result<T, ServerFnError<KratosError>>,
+) -> Option<(Option<String>, String)> {
+    match resp {
+        Err(ServerFnError::WrappedServerError(KratosError::FlowExpired {
+            use_flow_id,
+            message,
+        })) => Some((use_flow_id.clone(), message.clone())),
+        _ => None,
+    }
+}
+
+/// Before restarting an expired flow we keep what the user typed (i.e their email) but not their secrets,
+/// or anything tied to the old flow.
+pub fn retain_non_secret_fields(body: RwSignal<HashMap<String, String>>) {
+    body.update(|map| map.retain(|name, _| !SECRET_FIELDS.contains(&name.as_str())));
+}
+
+/// An array trait's value in the form body, a json array of strings.
+fn array_values(value: &str) -> Vec<String> {
+    serde_json::from_str::<Vec<String>>(value).unwrap_or_default()
+}
+
+fn set_array_values(body: RwSignal<HashMap<String, String>>, name: String, values: Vec<String>) {
+    let value = serde_json::to_string(&values).unwrap_or_default();
+    body.update(|map| {
+        _ = map.insert(name, value);
+    })
 }
 
 /// https://www.ory.sh/docs/kratos/concepts/ui-user-interface
-pub fn node_html(node: UiNode, body: RwSignal<HashMap<String, String>>) -> impl IntoView {
+/// `trait_inputs` comes from the identity schema, for the traits a plain input can't represent.
+pub fn node_html(
+    node: UiNode,
+    body: RwSignal<HashMap<String, String>>,
+    trait_inputs: &TraitInputs,
+) -> impl IntoView {
+    let locale = use_locale();
     // the label that goes as the child of our label
-    let label_text = node.meta.label.map(|text| text.text);
+    let label_text = node.meta.label.map(|text| translate(&text, locale));
     // each node MAY have messages (i.e password is bad, email is wrong form etc)
     let messages_html = view! {
         <For
@@ -23,10 +75,7 @@ pub fn node_html(node: UiNode, body: RwSignal<HashMap<String, String>>) -> impl
         // a unique key for each item
         key=|ui_text| ui_text.id
         // renders each item to a view
-        children=move |UiText { text,_type,.. }: UiText| {
-            // colored red, because we assume _type == error...
-            view!{<p style="color:red;">{text}</p>}
-        }
+        children=ui_text_html
       />
     };
 
@@ -38,6 +87,8 @@ pub fn node_html(node: UiNode, body: RwSignal<HashMap<String, String>>) -> impl
             required,
             _type,
             value,
+            onclick_trigger,
+            onload_trigger,
             // ? this is often empty for some reason ¿
             label: _label,
             ..
@@ -49,6 +100,11 @@ pub fn node_html(node: UiNode, body: RwSignal<HashMap<String, String>>) -> impl
             let _type_str = serde_json::to_string(&_type).unwrap();
             let name_clone = name.clone();
             let name_clone_2 = name.clone();
+            let onclick_trigger = onclick_trigger.as_ref().and_then(enum_str);
+            // i.e passkey autocomplete, started once the page is interactive.
+            if let Some(trigger) = onload_trigger.as_ref().and_then(enum_str) {
+                create_effect(move |_| call_trigger(trigger.clone(), TRIGGER_ATTEMPTS));
+            }
             let value = if let Some(serde_json::Value::String(value)) = value {
                 value
             } else if value.is_none() {
@@ -63,37 +119,165 @@ pub fn node_html(node: UiNode, body: RwSignal<HashMap<String, String>>) -> impl
                 }
             };
             if _type == UiNodeAttributesTypeEnum::Submit {
-                body.update(|map| {
-                    _ = map.insert(name.clone(), value.clone());
-                });
+                // A flow can have more than one submit (i.e "submit code" and "resend code")
+                // so we only put the value of the button that was actually clicked into the body.
+                let click_value = value.clone();
                 view! {
                     // will be something like value="password" name="method"
                     // or value="oidc" name="method"
-                    <input type="hidden" value=value name=name/>
-                    <input type="submit" value=label/>
+                    <button type="submit" name=name value=value disabled=disabled on:click=move|_|{
+                        let name = name_clone.clone();
+                        let value = click_value.clone();
+                        body.update(|map|{_=map.insert(name,value);});
+                        if let Some(trigger) = onclick_trigger.clone() {
+                            call_trigger(trigger, 0);
+                        }
+                    }>{label}</button>
                 }
                 .into_view()
-            } else if _type != UiNodeAttributesTypeEnum::Hidden {
+            } else if _type == UiNodeAttributesTypeEnum::Button {
+                // i.e webauthn_login_trigger, the script it triggers fills in a hidden input and submits the form itself.
                 view! {
-                  <fieldset>
-                    <label>
-                       <span>{&label}</span>
-                      <input name=name
-                      // we use replace here and in autocomplete because serde_json adds double quotes for some reason?
-                      type=_type_str.replace("\"","")
-                      value=move||body.get().get(&name_clone_2).cloned().unwrap_or_default()
-                      autocomplete=autocomplete.replace("\"","")
-                    disabled=disabled
-                    required=required placeholder=label
-                      on:input=move |ev|{
-                        let name = name_clone.clone();
-                        body.update(|map|{_=map.insert(name,event_target_value(&ev));})
-                      }
-                        />
-                    </label>
-                  </fieldset>
+                    <button type="button" name=name value=value disabled=disabled on:click=move|_|{
+                        match onclick_trigger.clone() {
+                            Some(trigger) => call_trigger(trigger, 0),
+                            None => leptos::logging::warn!("{} has no trigger", name_clone),
+                        }
+                    }>{label}</button>
                 }
                 .into_view()
+            } else if _type != UiNodeAttributesTypeEnum::Hidden {
+                // Kratos fills in values it already knows (i.e identity traits in the settings flow),
+                // but we don't want to overwrite anything the user has typed.
+                if !value.is_empty() {
+                    body.update(|map| {
+                        _ = map.entry(name.clone()).or_insert(value.clone());
+                    });
+                }
+                // the value model, the form body keeps every input (and the body we submit) in sync.
+                let current = Signal::derive(move || {
+                    body.with(|map| map.get(&name_clone_2).cloned().unwrap_or_default())
+                });
+                let trait_input = trait_inputs.get(&name).cloned();
+                if _type == UiNodeAttributesTypeEnum::Checkbox {
+                    view! {
+                      <fieldset>
+                        <label>
+                          <input type="checkbox" name=name
+                          prop:checked=move || current.get() == "true"
+                          disabled=disabled
+                          required=required
+                          on:change=move |ev|{
+                            let name = name_clone.clone();
+                            body.update(|map|{_=map.insert(name,event_target_checked(&ev).to_string());})
+                          }
+                          />
+                          <span>{&label}</span>
+                        </label>
+                      </fieldset>
+                    }
+                    .into_view()
+                } else if let Some(TraitInput {
+                    options,
+                    multiple: false,
+                }) = trait_input
+                {
+                    // an enum, one of the options.
+                    view! {
+                      <fieldset>
+                        <label>
+                          <span>{&label}</span>
+                          <select name=name disabled=disabled required=required
+                          on:change=move |ev|{
+                            let name = name_clone.clone();
+                            body.update(|map|{_=map.insert(name,event_target_value(&ev));})
+                          }>
+                            <option value="" prop:selected=move || current.get().is_empty()>"--"</option>
+                            {options.into_iter().map(|option|{
+                                let selected = option.clone();
+                                view!{<option value=option.clone() prop:selected=move || current.get() == selected>{option}</option>}
+                            }).collect_view()}
+                          </select>
+                        </label>
+                      </fieldset>
+                    }
+                    .into_view()
+                } else if let Some(TraitInput {
+                    options,
+                    multiple: true,
+                }) = trait_input
+                {
+                    if options.is_empty() {
+                        // an array of anything, comma separated.
+                        view! {
+                          <fieldset>
+                            <label>
+                              <span>{&label}</span>
+                              <input type="text" name=name disabled=disabled required=required placeholder=label.clone()
+                              value=move || array_values(&current.get()).join(", ")
+                              on:change=move |ev|{
+                                let values = event_target_value(&ev)
+                                    .split(',')
+                                    .map(|value| value.trim().to_string())
+                                    .filter(|value| !value.is_empty())
+                                    .collect();
+                                set_array_values(body, name_clone.clone(), values);
+                              }/>
+                            </label>
+                          </fieldset>
+                        }
+                        .into_view()
+                    } else {
+                        // an array of enums, any of the options.
+                        view! {
+                          <fieldset>
+                            <legend>{&label}</legend>
+                            {options.into_iter().map(|option|{
+                                let checked = option.clone();
+                                let toggled = option.clone();
+                                let name = name_clone.clone();
+                                view!{
+                                  <label>
+                                    <input type="checkbox" disabled=disabled
+                                    prop:checked=move || array_values(&current.get()).contains(&checked)
+                                    on:change=move |ev|{
+                                        let mut values = array_values(&current.get_untracked());
+                                        values.retain(|value| *value != toggled);
+                                        if event_target_checked(&ev) {
+                                            values.push(toggled.clone());
+                                        }
+                                        set_array_values(body, name.clone(), values);
+                                    }/>
+                                    <span>{option}</span>
+                                  </label>
+                                }
+                            }).collect_view()}
+                          </fieldset>
+                        }
+                        .into_view()
+                    }
+                } else {
+                    view! {
+                      <fieldset>
+                        <label>
+                           <span>{&label}</span>
+                          <input name=name
+                          // we use replace here and in autocomplete because serde_json adds double quotes for some reason?
+                          type=_type_str.replace("\"","")
+                          value=move || current.get()
+                          autocomplete=autocomplete.replace("\"","")
+                        disabled=disabled
+                        required=required placeholder=label
+                          on:input=move |ev|{
+                            let name = name_clone.clone();
+                            body.update(|map|{_=map.insert(name,event_target_value(&ev));})
+                          }
+                            />
+                        </label>
+                      </fieldset>
+                    }
+                    .into_view()
+                }
             } else {
                 body.update(|map| {
                     _ = map.insert(name.clone(), value.clone());
@@ -102,7 +286,7 @@ pub fn node_html(node: UiNode, body: RwSignal<HashMap<String, String>>) -> impl
             }
         }
         UiNodeAttributes::UiNodeAnchorAttributes { href, id, title } => {
-            let inner = title.text;
+            let inner = translate(&title, locale);
             view! {<a href=href id=id>{inner}</a>}.into_view()
         }
         UiNodeAttributes::UiNodeImageAttributes {
@@ -111,23 +295,672 @@ pub fn node_html(node: UiNode, body: RwSignal<HashMap<String, String>>) -> impl
             src,
             width,
         } => view! {<img src=src height=height width=width id=id/>}.into_view(),
-        UiNodeAttributes::UiNodeScriptAttributes { .. } => view! {script not supported}.into_view(),
-        UiNodeAttributes::UiNodeTextAttributes {
+        // i.e webauthn.js, which defines the functions our inputs' triggers call.
+        UiNodeAttributes::UiNodeScriptAttributes {
+            crossorigin,
             id,
-            text:
-                box UiText {
-                    // not sure how to make use of context yet.
-                    context: _context,
-                    // redundant id?
-                    id: _id,
-                    text,
-                    // This could be, info, error, success. i.e context for msg responses on bad input etc
-                    _type,
-                },
-        } => view! {<p id=id>{text}</p>}.into_view(),
+            integrity,
+            nonce,
+            referrerpolicy,
+            src,
+            _type,
+            ..
+        } => {
+            // when we serve a CSP with a nonce it's ours that lets the script run, not the one Kratos made.
+            let nonce = use_nonce().map_or(nonce, |nonce| nonce.to_string());
+            view! {
+                <script src=src id=id integrity=integrity nonce=nonce crossorigin=crossorigin
+                referrerpolicy=referrerpolicy type=_type></script>
+            }
+            .into_view()
+        }
+        // i.e the lookup secrets to write down, their context has the secrets.
+        UiNodeAttributes::UiNodeTextAttributes { id, text } => {
+            view! {<p id=id>{translate(&text, locale)}</p>}.into_view()
+        }
     };
     view! {
         {node_html}
         {messages_html}
     }
 }
+
+/// Tries for a couple of seconds, long enough for a script node loaded alongside the input.
+const TRIGGER_ATTEMPTS: u8 = 20;
+
+/// Kratos' enums as the strings they serialize to, i.e `aal2`, `password` or a trigger's function name like
+/// `oryWebAuthnLogin`.
+pub fn enum_str<T: Serialize>(value: &T) -> Option<String> {
+    serde_json::to_value(value).ok()?.as_str().map(String::from)
+}
+
+/// Kratos' script nodes define a function on window for every trigger, i.e `oryWebAuthnLogin` is
+/// `window.__oryWebAuthnLogin()`. The script might still be loading so we retry `attempts` more times.
+/// Only ever called from event handlers and effects, so only in the browser.
+fn call_trigger(trigger: String, attempts: u8) {
+    use wasm_bindgen::JsCast;
+    let name = format!("__{trigger}");
+    let function = js_sys::Reflect::get(&window(), &name.as_str().into())
+        .ok()
+        .and_then(|function| function.dyn_into::<js_sys::Function>().ok());
+    match function {
+        Some(function) => {
+            if let Err(err) = function.call0(&window()) {
+                leptos::logging::error!("{name} failed: {err:?}");
+            }
+        }
+        None if attempts > 0 => set_timeout(
+            move || call_trigger(trigger, attempts - 1),
+            std::time::Duration::from_millis(100),
+        ),
+        None => leptos::logging::error!("{name} isn't defined, did it's script node load?"),
+    }
+}
+
+/// Shows the inputs named in `names` but disables them, their value is still submitted from the page's body.
+/// i.e a refresh login sends the identifier hidden, but the user should see who they're confirming they are.
+pub fn lock_nodes(nodes: Vec<UiNode>, names: &[&str]) -> Vec<UiNode> {
+    if names.is_empty() {
+        return nodes;
+    }
+    nodes
+        .into_iter()
+        .map(|mut node| {
+            if let UiNodeAttributes::UiNodeInputAttributes {
+                name,
+                _type,
+                disabled,
+                ..
+            } = &mut *node.attributes
+            {
+                if names.contains(&name.as_str()) {
+                    if *_type == UiNodeAttributesTypeEnum::Hidden {
+                        *_type = UiNodeAttributesTypeEnum::Text;
+                        // Kratos' id for the identifier label, so it's translated like any other.
+                        node.meta.label.get_or_insert_with(|| {
+                            Box::new(UiText {
+                                context: None,
+                                id: 1070004,
+                                text: String::from("ID"),
+                                _type: ui_text::TypeEnum::Info,
+                            })
+                        });
+                    }
+                    *disabled = true;
+                }
+            }
+            node
+        })
+        .collect()
+}
+
+/// One method's nodes along with the shared `default` ones (csrf_token, the identifier...), rendered as one form.
+#[derive(Clone, Debug, PartialEq)]
+pub struct NodeGroup {
+    pub group: GroupEnum,
+    pub title: &'static str,
+    pub nodes: Vec<UiNode>,
+}
+
+pub fn group_title(group: &GroupEnum) -> &'static str {
+    match group {
+        GroupEnum::Default => "",
+        GroupEnum::Password => "Password",
+        GroupEnum::Oidc => "Social Sign In",
+        GroupEnum::Profile => "Profile",
+        GroupEnum::Link => "Link",
+        GroupEnum::Code => "Email Code",
+        GroupEnum::Totp => "Authenticator App",
+        GroupEnum::LookupSecret => "Backup Recovery Codes",
+        GroupEnum::Webauthn => "Security Key",
+        GroupEnum::Passkey => "Passkey",
+        _ => "",
+    }
+}
+
+fn is_hidden(node: &UiNode) -> bool {
+    matches!(
+        &*node.attributes,
+        UiNodeAttributes::UiNodeInputAttributes {
+            _type: UiNodeAttributesTypeEnum::Hidden,
+            ..
+        }
+    )
+}
+
+/// Partitions a flow's nodes by group (method) with the `default` nodes shared into every group, for `oidc` only the
+/// hidden ones.
+/// `groups` picks which groups to show and in which order, each titled by [group_title]. When it's empty every group
+/// is shown in the order Kratos sent them.
+pub fn group_nodes(nodes: Vec<UiNode>, groups: &[GroupEnum]) -> Vec<NodeGroup> {
+    let (default_nodes, nodes): (Vec<UiNode>, Vec<UiNode>) = nodes
+        .into_iter()
+        .partition(|node| node.group == GroupEnum::Default);
+    let groups = if groups.is_empty() {
+        let mut found: Vec<GroupEnum> = Vec::new();
+        for node in &nodes {
+            if !found.contains(&node.group) {
+                found.push(node.group.clone());
+            }
+        }
+        found
+    } else {
+        groups.to_vec()
+    };
+    let node_groups = groups
+        .into_iter()
+        .filter_map(|group| {
+            let group_nodes = nodes
+                .iter()
+                .filter(|node| node.group == group)
+                .cloned()
+                .collect::<Vec<UiNode>>();
+            if group_nodes.is_empty() {
+                return None;
+            }
+            // a provider button only needs the csrf_token, a required identifier would stop it from submitting.
+            let shared_nodes = default_nodes
+                .iter()
+                .filter(|node| group != GroupEnum::Oidc || is_hidden(node))
+                .cloned();
+            Some(NodeGroup {
+                title: group_title(&group),
+                group,
+                nodes: shared_nodes.chain(group_nodes).collect(),
+            })
+        })
+        .collect::<Vec<NodeGroup>>();
+    // i.e the final step of a flow, nothing but messages and maybe a link.
+    if node_groups.is_empty() && !default_nodes.is_empty() {
+        return vec![NodeGroup {
+            group: GroupEnum::Default,
+            title: "",
+            nodes: default_nodes,
+        }];
+    }
+    node_groups
+}
+
+/// The json body we submit to Kratos, see [BodyBuilder].
+/// Server functions take it with `#[server(input = Json)]`, url encoding would turn it back into strings.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct FlowBody(pub serde_json::Map<String, serde_json::Value>);
+
+impl FlowBody {
+    /// Takes one of our own fields (i.e action) out before the body goes to Kratos.
+    pub fn remove(&mut self, name: &str) -> Option<String> {
+        match self.0.remove(name) {
+            Some(serde_json::Value::String(value)) => Some(value),
+            _ => None,
+        }
+    }
+}
+
+/// What json type a field has in the body Kratos expects.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum FieldKind {
+    String,
+    Bool,
+    Number,
+    /// Kept as a json array in the form body, see [array_values].
+    Array,
+}
+
+/// Our form body is all strings keyed by node name, Kratos wants json shaped like the identity schema.
+/// i.e `traits.name.first` becomes `{"traits":{"name":{"first":..}}}`, checkboxes become booleans, number inputs
+/// become numbers, array traits become arrays and buttons like `lookup_secret_confirm` send their `true` back as a
+/// boolean.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct BodyBuilder {
+    kinds: HashMap<String, FieldKind>,
+}
+
+impl BodyBuilder {
+    pub fn from_nodes(nodes: &[UiNode], trait_inputs: &TraitInputs) -> Self {
+        let kinds = nodes
+            .iter()
+            .filter_map(|node| match &*node.attributes {
+                UiNodeAttributes::UiNodeInputAttributes {
+                    name, _type, value, ..
+                } => {
+                    let multiple = trait_inputs.get(name).is_some_and(|input| input.multiple);
+                    let kind = match (_type, value) {
+                        _ if multiple => FieldKind::Array,
+                        (_, Some(serde_json::Value::Array(_))) => FieldKind::Array,
+                        (UiNodeAttributesTypeEnum::Checkbox, _) => FieldKind::Bool,
+                        (UiNodeAttributesTypeEnum::Number, _) => FieldKind::Number,
+                        (_, Some(serde_json::Value::Bool(_))) => FieldKind::Bool,
+                        (_, Some(serde_json::Value::Number(_))) => FieldKind::Number,
+                        _ => FieldKind::String,
+                    };
+                    Some((name.clone(), kind))
+                }
+                _ => None,
+            })
+            .collect();
+        Self { kinds }
+    }
+
+    /// Only the fields of our nodes go in, plus the ones our server functions take out again (action, return_to).
+    pub fn build(&self, body: &HashMap<String, String>) -> FlowBody {
+        let mut map = serde_json::Map::new();
+        for (name, value) in body {
+            let kind = match self.kinds.get(name) {
+                Some(kind) => *kind,
+                None if name == "action" || name == "return_to" => FieldKind::String,
+                None => continue,
+            };
+            let value = match kind {
+                FieldKind::String => serde_json::Value::String(value.clone()),
+                FieldKind::Bool => serde_json::Value::Bool(matches!(value.as_str(), "true" | "on")),
+                // an empty number input means no value, not an invalid one.
+                FieldKind::Number if value.is_empty() => continue,
+                FieldKind::Array => serde_json::Value::Array(
+                    array_values(value)
+                        .into_iter()
+                        .map(serde_json::Value::String)
+                        .collect(),
+                ),
+                FieldKind::Number => match value.parse::<i64>() {
+                    Ok(number) => number.into(),
+                    // let Kratos tell the user it's not a number.
+                    Err(_) => value
+                        .parse::<f64>()
+                        .ok()
+                        .and_then(serde_json::Number::from_f64)
+                        .map(serde_json::Value::Number)
+                        .unwrap_or(serde_json::Value::String(value.clone())),
+                },
+            };
+            insert_dotted(&mut map, name, value);
+        }
+        FlowBody(map)
+    }
+}
+
+fn insert_dotted(
+    map: &mut serde_json::Map<String, serde_json::Value>,
+    name: &str,
+    value: serde_json::Value,
+) {
+    match name.split_once('.') {
+        Some((key, rest)) => {
+            let inner = map
+                .entry(key)
+                .or_insert_with(|| serde_json::Value::Object(serde_json::Map::new()));
+            if !inner.is_object() {
+                *inner = serde_json::Value::Object(serde_json::Map::new());
+            }
+            if let serde_json::Value::Object(inner) = inner {
+                insert_dotted(inner, rest, value);
+            }
+        }
+        None => {
+            _ = map.insert(name.to_string(), value);
+        }
+    }
+}
+
+/// A form per group (see [group_nodes]) all sharing the page's body, so the identifier typed into one form shows up
+/// in the others. Submitting a form only sends it's own fields (plus what the server function needs i.e action).
+/// `action` and `method` are the flow's, for when a script submits a form itself (webauthn) and goes straight to Kratos.
+pub fn node_groups_html(
+    nodes: Vec<UiNode>,
+    action: &str,
+    method: &str,
+    groups: &[GroupEnum],
+    body: RwSignal<HashMap<String, String>>,
+    trait_inputs: &TraitInputs,
+    on_submit: impl Fn(FlowBody) + Clone + 'static,
+) -> View {
+    let node_groups = group_nodes(nodes, groups);
+    // a heading for a single form is just noise.
+    let show_titles = node_groups.len() > 1;
+    node_groups
+        .into_iter()
+        .map(|NodeGroup { title, nodes, .. }| {
+            let builder = BodyBuilder::from_nodes(&nodes, trait_inputs);
+            let on_submit = on_submit.clone();
+            let action = action.to_string();
+            let method = method.to_string();
+            let form_inner_html = nodes
+                .into_iter()
+                .map(|node| node_html(node, body, trait_inputs))
+                .collect_view();
+            view! {
+                {(show_titles && !title.is_empty()).then(|| view!{<h2>{title}</h2>})}
+                <form action=action method=method on:submit=move|e|{
+                    e.prevent_default();
+                    e.stop_propagation();
+                    on_submit(body.with_untracked(|map| builder.build(map)));
+                }>
+                {form_inner_html}
+                </form>
+            }
+        })
+        .collect_view()
+}
+
+/// Puts `?flow=FLOW_ID` in the address bar without the router noticing, it would only refetch the flow we already have.
+/// Only ever called from effects, so only in the browser.
+pub fn keep_flow_id_in_url(flow_id: &str) {
+    let location = window().location();
+    let (Ok(path), Ok(search)) = (location.pathname(), location.search()) else {
+        return;
+    };
+    let search = search.trim_start_matches('?');
+    let flow_param = format!("flow={flow_id}");
+    if search.split('&').any(|param| param == flow_param) {
+        return;
+    }
+    let params = search
+        .split('&')
+        .filter(|param| !param.is_empty() && !param.starts_with("flow="))
+        .map(String::from)
+        .chain(std::iter::once(flow_param))
+        .collect::<Vec<String>>();
+    let url = format!("{path}?{}", params.join("&"));
+    if let Err(err) = window().history().and_then(|history| {
+        history.replace_state_with_url(&wasm_bindgen::JsValue::NULL, "", Some(&url))
+    }) {
+        leptos::logging::error!("Failed to put the flow id in the url: {err:?}");
+    }
+}
+
+/// leptos_router's `Redirect` is for routes on our app, but Kratos often wants the browser to go to one of it's
+/// own public endpoints (i.e a refresh login) so we need to leave the app entirely.
+#[component]
+pub fn ExternalRedirect(url: String) -> impl IntoView {
+    #[cfg(feature = "ssr")]
+    {
+        leptos_axum::redirect(&url);
+    }
+    #[cfg(not(feature = "ssr"))]
+    {
+        if let Err(err) = window().location().set_href(&url) {
+            leptos::logging::error!("Failed to redirect to {url}: {err:?}");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn input(group: &str, name: &str, _type: &str, value: serde_json::Value) -> UiNode {
+        serde_json::from_value(serde_json::json!({
+            "type": "input",
+            "group": group,
+            "attributes": {
+                "node_type": "input",
+                "name": name,
+                "type": _type,
+                "value": value,
+                "disabled": false,
+            },
+            "messages": [],
+            "meta": {},
+        }))
+        .unwrap()
+    }
+
+    fn names(group: &NodeGroup) -> Vec<&str> {
+        group
+            .nodes
+            .iter()
+            .filter_map(|node| match &*node.attributes {
+                UiNodeAttributes::UiNodeInputAttributes { name, .. } => Some(name.as_str()),
+                _ => None,
+            })
+            .collect()
+    }
+
+    fn login_nodes() -> Vec<UiNode> {
+        vec![
+            input(
+                "default",
+                "csrf_token",
+                "hidden",
+                serde_json::json!("token"),
+            ),
+            input("default", "identifier", "text", serde_json::Value::Null),
+            input("password", "password", "password", serde_json::Value::Null),
+            input(
+                "password",
+                "method",
+                "submit",
+                serde_json::json!("password"),
+            ),
+            input("oidc", "provider", "submit", serde_json::json!("mock")),
+        ]
+    }
+
+    #[test]
+    fn every_group_in_kratos_order_with_the_default_nodes() {
+        let groups = group_nodes(login_nodes(), &[]);
+        assert_eq!(
+            groups
+                .iter()
+                .map(|group| (group.group.clone(), group.title))
+                .collect::<Vec<_>>(),
+            vec![
+                (GroupEnum::Password, "Password"),
+                (GroupEnum::Oidc, "Social Sign In"),
+            ]
+        );
+        assert_eq!(
+            names(&groups[0]),
+            vec!["csrf_token", "identifier", "password", "method"]
+        );
+        // a required identifier would stop the provider button from submitting.
+        assert_eq!(names(&groups[1]), vec!["csrf_token", "provider"]);
+    }
+
+    #[test]
+    fn only_the_groups_asked_for_in_their_order() {
+        let groups = group_nodes(
+            login_nodes(),
+            &[GroupEnum::Totp, GroupEnum::Oidc, GroupEnum::Password],
+        );
+        assert_eq!(
+            groups
+                .iter()
+                .map(|group| group.group.clone())
+                .collect::<Vec<_>>(),
+            vec![GroupEnum::Oidc, GroupEnum::Password]
+        );
+    }
+
+    #[test]
+    fn a_flow_with_only_default_nodes_is_one_group() {
+        let nodes = vec![input(
+            "default",
+            "csrf_token",
+            "hidden",
+            serde_json::json!("token"),
+        )];
+        let groups = group_nodes(nodes, &[GroupEnum::Password]);
+        assert_eq!(groups.len(), 1);
+        assert_eq!(groups[0].group, GroupEnum::Default);
+        assert_eq!(names(&groups[0]), vec!["csrf_token"]);
+    }
+
+    fn body(fields: &[(&str, &str)]) -> HashMap<String, String> {
+        fields
+            .iter()
+            .map(|(name, value)| (name.to_string(), value.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn dotted_names_nest() {
+        let nodes = vec![
+            input("profile", "traits.email", "email", serde_json::Value::Null),
+            input(
+                "profile",
+                "traits.name.first",
+                "text",
+                serde_json::Value::Null,
+            ),
+            input(
+                "profile",
+                "traits.name.last",
+                "text",
+                serde_json::Value::Null,
+            ),
+        ];
+        let builder = BodyBuilder::from_nodes(&nodes, &TraitInputs::default());
+        let FlowBody(map) = builder.build(&body(&[
+            ("traits.email", "user@example.com"),
+            ("traits.name.first", "Ada"),
+            ("traits.name.last", "Lovelace"),
+        ]));
+        assert_eq!(
+            serde_json::Value::Object(map),
+            serde_json::json!({
+                "traits": {
+                    "email": "user@example.com",
+                    "name": {"first": "Ada", "last": "Lovelace"},
+                }
+            })
+        );
+    }
+
+    #[test]
+    fn checkboxes_and_boolean_buttons_are_booleans() {
+        let nodes = vec![
+            input(
+                "profile",
+                "traits.newsletter",
+                "checkbox",
+                serde_json::Value::Null,
+            ),
+            input(
+                "profile",
+                "traits.terms",
+                "checkbox",
+                serde_json::Value::Null,
+            ),
+            input(
+                "lookup_secret",
+                "lookup_secret_confirm",
+                "submit",
+                serde_json::json!(true),
+            ),
+        ];
+        let builder = BodyBuilder::from_nodes(&nodes, &TraitInputs::default());
+        let FlowBody(map) = builder.build(&body(&[
+            ("traits.newsletter", "on"),
+            ("traits.terms", "false"),
+            ("lookup_secret_confirm", "true"),
+        ]));
+        assert_eq!(
+            serde_json::Value::Object(map),
+            serde_json::json!({
+                "traits": {"newsletter": true, "terms": false},
+                "lookup_secret_confirm": true,
+            })
+        );
+    }
+
+    #[test]
+    fn numbers_are_numbers_unless_they_are_not() {
+        let nodes = vec![
+            input("profile", "traits.age", "number", serde_json::Value::Null),
+            input(
+                "profile",
+                "traits.height",
+                "number",
+                serde_json::Value::Null,
+            ),
+            input(
+                "profile",
+                "traits.shoe_size",
+                "number",
+                serde_json::Value::Null,
+            ),
+            input(
+                "profile",
+                "traits.siblings",
+                "number",
+                serde_json::Value::Null,
+            ),
+        ];
+        let builder = BodyBuilder::from_nodes(&nodes, &TraitInputs::default());
+        let FlowBody(map) = builder.build(&body(&[
+            ("traits.age", "42"),
+            ("traits.height", "1.75"),
+            ("traits.shoe_size", "big"),
+            ("traits.siblings", ""),
+        ]));
+        // Kratos tells the user "big" isn't a number, an empty input is no value at all.
+        assert_eq!(
+            serde_json::Value::Object(map),
+            serde_json::json!({"traits": {"age": 42, "height": 1.75, "shoe_size": "big"}})
+        );
+    }
+
+    #[test]
+    fn array_traits_are_arrays() {
+        let nodes = vec![
+            input(
+                "profile",
+                "traits.languages",
+                "text",
+                serde_json::Value::Null,
+            ),
+            input(
+                "profile",
+                "traits.emails",
+                "text",
+                serde_json::json!(["a@example.com"]),
+            ),
+        ];
+        let trait_inputs = TraitInputs(HashMap::from([(
+            String::from("traits.languages"),
+            TraitInput {
+                options: vec![String::from("en"), String::from("de")],
+                multiple: true,
+            },
+        )]));
+        let builder = BodyBuilder::from_nodes(&nodes, &trait_inputs);
+        let FlowBody(map) = builder.build(&body(&[
+            ("traits.languages", r#"["en","de"]"#),
+            ("traits.emails", r#"["a@example.com","b@example.com"]"#),
+        ]));
+        assert_eq!(
+            serde_json::Value::Object(map),
+            serde_json::json!({
+                "traits": {
+                    "languages": ["en", "de"],
+                    "emails": ["a@example.com", "b@example.com"],
+                }
+            })
+        );
+    }
+
+    #[test]
+    fn only_our_nodes_and_our_own_fields_go_in() {
+        let nodes = vec![input(
+            "password",
+            "password",
+            "password",
+            serde_json::Value::Null,
+        )];
+        let builder = BodyBuilder::from_nodes(&nodes, &TraitInputs::default());
+        let FlowBody(map) = builder.build(&body(&[
+            ("password", "hunter2"),
+            ("identifier", "typed into another form"),
+            ("action", "http://127.0.0.1:4433/self-service/login?flow=1"),
+            ("return_to", "/settings"),
+        ]));
+        assert_eq!(
+            serde_json::Value::Object(map),
+            serde_json::json!({
+                "password": "hunter2",
+                "action": "http://127.0.0.1:4433/self-service/login?flow=1",
+                "return_to": "/settings",
+            })
+        );
+    }
+}
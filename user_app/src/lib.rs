@@ -1,11 +1,31 @@
 #![feature(box_patterns)]
 pub mod app;
+#[cfg(feature = "ssr")]
+pub mod auth;
+#[cfg(feature = "ssr")]
+pub mod config;
+pub mod continue_with;
+pub mod devices;
 pub mod error_template;
 #[cfg(feature = "ssr")]
 pub mod fileserv;
+pub mod flow_error;
+pub mod flow_page;
+pub mod i18n;
+pub mod identity_schema;
+#[cfg(feature = "ssr")]
+pub mod kratos_client;
+pub mod kratos_error;
 pub mod kratos_utils;
 pub mod login;
+pub mod logout;
+pub mod recovery;
 pub mod registration;
+#[cfg(feature = "ssr")]
+pub mod session;
+pub mod settings;
+#[cfg(feature = "ssr")]
+pub mod state;
 pub mod verification;
 
 use crate::error_template::{AppError, ErrorTemplate};
@@ -1,159 +1,144 @@
 use super::*;
 
-use crate::kratos_utils::*;
+#[cfg(feature = "ssr")]
+use crate::continue_with::{after_success, Destination};
+use crate::flow_page::{FlowPage, FlowResult, FlowStep, KratosFlow};
+#[cfg(feature = "ssr")]
+use crate::kratos_client::{FlowResponse, KratosClient};
+use crate::kratos_error::KratosError;
+use crate::kratos_utils::FlowBody;
 
+use ory_kratos_client::models::ui_node::GroupEnum;
+use ory_kratos_client::models::AuthenticatorAssuranceLevel;
 use ory_kratos_client::models::LoginFlow;
 use ory_kratos_client::models::UiContainer;
-use ory_kratos_client::models::UiText;
+use std::future::Future;
 #[cfg(feature = "ssr")]
 use tracing::debug;
 
-#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
-pub struct ViewableLoginFlow(LoginFlow);
-impl IntoView for ViewableLoginFlow {
-    fn into_view(self) -> View {
-        format!("{:?}", self).into_view()
-    }
-}
+/// `aal` of `aal2` is the second factor step for a session that only has a first factor,
+/// `refresh` asks for the credentials again even though there's a session (i.e before changing the password).
 #[tracing::instrument]
 #[server]
-pub async fn init_login() -> Result<ViewableLoginFlow, ServerFnError> {
-    let client = reqwest::ClientBuilder::new()
-        .cookie_store(true)
-        .redirect(reqwest::redirect::Policy::none())
-        .build()?;
-    // Get the csrf_token cookie.
-    let resp = client
-        .get("http://127.0.0.1:4433/self-service/login/browser")
-        .send()
-        .await?;
-    let first_cookie = resp
-        .cookies()
-        .next()
-        .ok_or(ServerFnError::new("Expecting a first cookie"))?;
-    let csrf_token = first_cookie.value();
-    let location = resp
-        .headers()
-        .get("Location")
-        .ok_or(ServerFnError::new("expecting location in headers"))?
-        .to_str()?;
-    // Parses the url and takes first query which will be flow=FLOW_ID and we get FLOW_ID at .1
-    let location_url = url::Url::parse(location)?;
-    let id = location_url
-        .query_pairs()
-        .next()
-        .ok_or(ServerFnError::new(
-            "Expecting query in location header value",
-        ))?
-        .1;
-    let set_cookie = resp
-        .headers()
-        .get("set-cookie")
-        .ok_or(ServerFnError::new("expecting set-cookie in headers"))?
-        .to_str()?;
-    let flow = client
-        .get("http://127.0.0.1:4433/self-service/login/flows")
-        .query(&[("id", id)])
-        .header("x-csrf-token", csrf_token)
-        .send()
+pub async fn init_login(
+    return_to: Option<String>,
+    aal: Option<String>,
+    refresh: bool,
+) -> Result<FlowStep<LoginFlow>, ServerFnError<KratosError>> {
+    let kratos = expect_context::<KratosClient>();
+    let mut query = Vec::new();
+    if let Some(aal) = aal.as_deref() {
+        query.push(("aal", aal));
+    }
+    if refresh {
+        query.push(("refresh", "true"));
+    }
+    let flow = kratos
+        .create_browser_flow::<LoginFlow>(return_to, &query)
         .await?
-        .json::<ViewableLoginFlow>()
-        .await?;
-    let opts = expect_context::<leptos_axum::ResponseOptions>();
-    opts.insert_header(
-        axum::http::HeaderName::from_static("set-cookie"),
-        axum::http::HeaderValue::from_str(set_cookie)?,
-    );
-    Ok(flow)
+        .into_flow()?;
+    debug!("{:#?}", flow);
+    Ok(FlowStep::Flow(flow))
 }
 
 #[tracing::instrument]
 #[server]
 pub async fn fetch_preexisting_login_flow(
     flow_id: String,
-) -> Result<ViewableLoginFlow, ServerFnError> {
-    let cookie_jar = leptos_axum::extract::<axum_extra::extract::CookieJar>().await?;
-    // because csrf_token has a lot of nonsense attached to it's name.
-    // i.e csrf_token8912490183409829084029384092....
-    let csrf_cookie = cookie_jar
-        .iter()
-        .filter(|cookie| cookie.name().contains("csrf_token"))
-        .next()
-        .ok_or(ServerFnError::new(
-            "Expecting a csrf_token cookie to already be set if fetching a pre-existing flow",
-        ))?;
-    let csrf_token = csrf_cookie.value();
-    let client = reqwest::ClientBuilder::new()
-        .redirect(reqwest::redirect::Policy::none())
-        .build()?;
-    let flow = client
-        .get("http://127.0.0.1:4433/self-service/login/flows")
-        .query(&[("id", flow_id)])
-        .header("x-csrf-token", csrf_token)
-        .header(
-            "cookie",
-            format!("{}={}", csrf_cookie.name(), csrf_cookie.value()),
-        )
-        .send()
-        .await?
-        .json::<ViewableLoginFlow>()
-        .await?;
+) -> Result<FlowStep<LoginFlow>, ServerFnError<KratosError>> {
+    let kratos = expect_context::<KratosClient>();
+    let flow = kratos.get_flow::<LoginFlow>(&flow_id).await?.into_flow()?;
     debug!("{:#?}", flow);
-    Ok(flow)
+    Ok(FlowStep::Flow(flow))
+}
+
+/// Returns the flow with validation messages (i.e wrong password), or where to go now that we're logged in.
+/// A one time code is two submits to the same flow (it's ui.action has the flow id): the identifier, after which Kratos
+/// emails the code and answers with the flow again now asking for the code (or a resend), and then the code.
+#[tracing::instrument]
+#[server(input = Json)]
+pub async fn login(body: FlowBody) -> Result<FlowStep<LoginFlow>, ServerFnError<KratosError>> {
+    let mut body = body;
+    let action = body
+        .remove("action")
+        .ok_or(KratosError::Unexpected(String::from(
+            "Can't find action on body.",
+        )))?;
+    // not part of kratos' body, it's the return_to of the flow so we know where to go on success.
+    let return_to = body.remove("return_to");
+    let kratos = expect_context::<KratosClient>();
+    match kratos.submit_flow::<LoginFlow>(&action, &body).await {
+        Ok(FlowResponse::Flow(flow)) => {
+            debug!("{:#?}", flow);
+            Ok(FlowStep::Flow(flow))
+        }
+        Ok(FlowResponse::Success(value)) => {
+            let destination = after_success(&kratos, &value, return_to);
+            debug!("logged in, continuing to {:?}", destination);
+            // a full page load, so everything is rendered with our new session.
+            Ok(FlowStep::Continue(Destination::Url(destination.href())))
+        }
+        // i.e a provider button, the provider sends the browser back to Kratos' callback which finishes the flow and
+        // redirects to the flow's return_to (or back here with ?flow= when something went wrong).
+        Err(KratosError::BrowserLocationChangeRequired {
+            redirect_browser_to,
+        }) => Ok(FlowStep::Continue(Destination::Url(redirect_browser_to))),
+        Err(err) => Err(err.into()),
+    }
+}
+
+/// The methods Kratos offers as a second factor, with no first factor methods to confuse things.
+const SECOND_FACTOR_GROUPS: &[GroupEnum] = &[
+    GroupEnum::Totp,
+    GroupEnum::LookupSecret,
+    GroupEnum::Webauthn,
+];
+
+impl KratosFlow for LoginFlow {
+    const TITLE: &'static str = "Login";
+
+    fn ui(&self) -> &UiContainer {
+        &self.ui
+    }
+    fn id(&self) -> &str {
+        &self.id
+    }
+    fn return_to(&self) -> Option<String> {
+        self.return_to.clone()
+    }
+    fn groups(&self) -> &'static [GroupEnum] {
+        match self.requested_aal {
+            Some(AuthenticatorAssuranceLevel::Aal2) => SECOND_FACTOR_GROUPS,
+            _ => Self::GROUPS,
+        }
+    }
+    /// A refresh login is for the identity that's already logged in.
+    fn locked(&self) -> &'static [&'static str] {
+        match self.refresh {
+            Some(true) => &["identifier"],
+            _ => &[],
+        }
+    }
+
+    /// ?aal=aal2 and ?refresh=true are passed on to Kratos, see [init_login].
+    fn init(return_to: Option<String>, query: ParamsMap) -> impl Future<Output = FlowResult<Self>> {
+        init_login(
+            return_to,
+            query.get("aal").cloned(),
+            query
+                .get("refresh")
+                .is_some_and(|refresh| refresh == "true"),
+        )
+    }
+    fn fetch(flow_id: String) -> impl Future<Output = FlowResult<Self>> {
+        fetch_preexisting_login_flow(flow_id)
+    }
+    fn submit(body: FlowBody) -> impl Future<Output = FlowResult<Self>> {
+        login(body)
+    }
 }
 
 #[component]
 pub fn LoginPage() -> impl IntoView {
-    let login_flow = create_resource(
-        || {
-            if let Some(flow_id) = use_query_map().get_untracked().get("flow").cloned() {
-                FlowStages::HandleFlow(flow_id)
-            } else {
-                FlowStages::Init
-            }
-        },
-        |variant| async move {
-            match variant {
-                FlowStages::HandleFlow(flow_id) => fetch_preexisting_login_flow(flow_id).await,
-                FlowStages::Init => init_login().await,
-            }
-        },
-    );
-    let body = create_rw_signal(HashMap::new());
-    view! {
-      <Suspense fallback=||view!{Loading Login Details}>
-        <ErrorBoundary fallback=|errors|format!("ERRORS: {:?}",errors.get()).into_view()>
-        {
-          move ||
-            login_flow.get().map(|resp|{
-                match resp {
-                    // TODO add Oauth using the flow args (see type docs)
-                    Ok(ViewableLoginFlow(LoginFlow{ui:box UiContainer{nodes,action,method,messages},..})) => {
-                        let form_inner_html = nodes.into_iter().map(|node|node_html(node,body)).collect_view();
-                        view!{
-                            <form method=method action=action>
-                            {form_inner_html}
-                            {messages.map(|messages|{
-                                view!{
-                                    <For
-                                        each=move || messages.clone().into_iter()
-                                        key=|text| text.id
-                                        children=move |text: UiText| {
-                                          view! {
-                                            <p id=text.id>{text.text}</p>
-                                          }
-                                        }
-                                    />
-                                }
-                            }).unwrap_or_default()}
-                            </form>
-                        }.into_view()
-                    },
-                    err => err.into_view(),
-                }
-            })
-          }
-        </ErrorBoundary>
-      </Suspense>
-    }
+    view! {<FlowPage<LoginFlow>/>}
 }
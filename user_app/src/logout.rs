@@ -0,0 +1,79 @@
+use super::*;
+
+#[cfg(feature = "ssr")]
+use crate::kratos_client::{kratos_json, kratos_ok, KratosClient};
+use crate::kratos_error::KratosError;
+use crate::kratos_utils::*;
+
+/// https://www.ory.sh/docs/reference/api#tag/frontend/operation/createBrowserLogoutFlow
+#[cfg(feature = "ssr")]
+#[derive(Debug, Deserialize)]
+struct LogoutFlow {
+    logout_token: String,
+}
+
+/// Logs the user out and returns where the browser should go afterwards.
+#[tracing::instrument]
+#[server]
+pub async fn logout() -> Result<String, ServerFnError<KratosError>> {
+    let cookie_jar = leptos_axum::extract::<axum_extra::extract::CookieJar>()
+        .await
+        .map_err(KratosError::from)?;
+    let session_cookie = cookie_jar
+        .get("ory_kratos_session")
+        .ok_or(KratosError::SessionInactive)?;
+    let kratos = expect_context::<KratosClient>();
+    let after_logout_url = kratos.config().after_logout_url.clone();
+    // Kratos needs the session to give us a logout token for it.
+    let resp = kratos
+        .get("self-service/logout/browser")
+        .query(&[("return_to", after_logout_url.as_str())])
+        .header(
+            "cookie",
+            format!("{}={}", session_cookie.name(), session_cookie.value()),
+        )
+        .send()
+        .await
+        .map_err(KratosError::from)?;
+    let LogoutFlow { logout_token } = kratos_json::<LogoutFlow>(resp).await?;
+    // With accept json Kratos answers 204 instead of redirecting us to logout.after.default_browser_return_url
+    let resp = kratos
+        .get("self-service/logout")
+        .query(&[("token", logout_token)])
+        .header(
+            "cookie",
+            format!("{}={}", session_cookie.name(), session_cookie.value()),
+        )
+        .send()
+        .await
+        .map_err(KratosError::from)?;
+    kratos_ok(resp).await?;
+    let opts = expect_context::<leptos_axum::ResponseOptions>();
+    opts.insert_header(
+        axum::http::HeaderName::from_static("cache-control"),
+        axum::http::HeaderValue::from_static("private, no-cache, no-store, must-revalidate"),
+    );
+    // The session is dead on Kratos' side, but the browser still holds the cookie on our origin.
+    opts.append_header(
+        axum::http::HeaderName::from_static("set-cookie"),
+        axum::http::HeaderValue::from_static(
+            "ory_kratos_session=; Path=/; Max-Age=0; Expires=Thu, 01 Jan 1970 00:00:00 GMT; HttpOnly",
+        ),
+    );
+    // logout.after.default_browser_return_url in kratos.yaml, unless AppConfig says otherwise.
+    Ok(after_logout_url.to_string())
+}
+
+#[component]
+pub fn LogoutButton() -> impl IntoView {
+    let logout = Action::<Logout, _>::server();
+    view! {
+        <button on:click=move|_|logout.dispatch(Logout{})>"Logout"</button>
+        {move || match logout.value().get() {
+            // a full page load, so nothing rendered with the old session sticks around.
+            Some(Ok(url)) => view!{<ExternalRedirect url/>}.into_view(),
+            Some(Err(err)) => view!{<p style="color:red;">{err.to_string()}</p>}.into_view(),
+            None => ().into_view(),
+        }}
+    }
+}
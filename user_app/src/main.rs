@@ -4,7 +4,10 @@ async fn main() {
     use axum::Router;
     use leptos::*;
     use leptos_axum::{generate_route_list, LeptosRoutes};
-    use user_app::{app::*, fileserv::file_and_error_handler};
+    use user_app::{
+        app::*, auth::auth_guard, config::AppConfig, fileserv::file_and_error_handler,
+        kratos_client::KratosClient, state::AppState,
+    };
     tracing_subscriber::fmt()
         .with_max_level(tracing::Level::DEBUG)
         .compact()
@@ -20,11 +23,32 @@ async fn main() {
     let addr = leptos_options.site_addr;
     let routes = generate_route_list(App);
 
+    // Where Kratos lives, see config.rs for the env vars.
+    let config = AppConfig::load().unwrap_or_else(|err| panic!("Invalid configuration: {err}"));
+    logging::log!("{:#?}", config);
+    let kratos = KratosClient::new(config.clone()).expect("Expecting to build a reqwest client");
+    let state = AppState {
+        leptos_options,
+        config,
+        kratos: kratos.clone(),
+    };
+
     // build our application with a route
     let app = Router::new()
-        .leptos_routes(&leptos_options, routes, App)
+        .leptos_routes_with_context(
+            &state,
+            routes,
+            {
+                let state = state.clone();
+                move || state.provide_context()
+            },
+            App,
+        )
         .fallback(file_and_error_handler)
-        .with_state(leptos_options)
+        .with_state(state)
+        .layer(axum::middleware::from_fn(auth_guard))
+        // for our extractors and middleware, which don't have Leptos' context.
+        .layer(axum::Extension(kratos))
         .layer(tower_http::trace::TraceLayer::new_for_http());
 
     let listener = tokio::net::TcpListener::bind(&addr).await.unwrap();
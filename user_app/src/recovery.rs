@@ -0,0 +1,106 @@
+use super::*;
+
+#[cfg(feature = "ssr")]
+use crate::continue_with::Destination;
+use crate::flow_page::{FlowPage, FlowResult, FlowStep, KratosFlow};
+#[cfg(feature = "ssr")]
+use crate::kratos_client::{FlowResponse, KratosClient};
+use crate::kratos_error::KratosError;
+use crate::kratos_utils::FlowBody;
+
+use ory_kratos_client::models::RecoveryFlow;
+use ory_kratos_client::models::UiContainer;
+use std::future::Future;
+#[cfg(feature = "ssr")]
+use tracing::debug;
+
+#[tracing::instrument]
+#[server]
+pub async fn init_recovery(
+    return_to: Option<String>,
+) -> Result<FlowStep<RecoveryFlow>, ServerFnError<KratosError>> {
+    let kratos = expect_context::<KratosClient>();
+    let flow = kratos
+        .create_browser_flow::<RecoveryFlow>(return_to, &[])
+        .await?
+        .into_flow()?;
+    debug!("{:#?}", flow);
+    Ok(FlowStep::Flow(flow))
+}
+
+#[tracing::instrument]
+#[server]
+pub async fn fetch_preexisting_recovery_flow(
+    flow_id: String,
+) -> Result<FlowStep<RecoveryFlow>, ServerFnError<KratosError>> {
+    let kratos = expect_context::<KratosClient>();
+    let flow = kratos
+        .get_flow::<RecoveryFlow>(&flow_id)
+        .await?
+        .into_flow()?;
+    debug!("{:#?}", flow);
+    Ok(FlowStep::Flow(flow))
+}
+
+/// The first step asks for the email, the second step (same flow) asks for the code we emailed.
+/// Once Kratos accepts the code and issues a privileged session we continue on the settings flow it made.
+#[tracing::instrument]
+#[server(input = Json)]
+pub async fn recover(body: FlowBody) -> Result<FlowStep<RecoveryFlow>, ServerFnError<KratosError>> {
+    let mut body = body;
+    let action = body
+        .remove("action")
+        .ok_or(KratosError::Unexpected(String::from(
+            "Can't find action on body.",
+        )))?;
+    let kratos = expect_context::<KratosClient>();
+    // once the code is accepted Kratos sets our privileged ory_kratos_session and answers with a 422.
+    match kratos.submit_flow::<RecoveryFlow>(&action, &body).await {
+        Ok(FlowResponse::Flow(flow)) => {
+            debug!("{:#?}", flow);
+            Ok(FlowStep::Flow(flow))
+        }
+        Err(KratosError::BrowserLocationChangeRequired {
+            redirect_browser_to,
+        }) => {
+            // redirect_browser_to is the absolute settings ui_url from kratos.yaml, which is one of our routes.
+            let destination = Destination::from_url(&redirect_browser_to, kratos.config());
+            debug!("recovered, continuing at {:?}", destination);
+            Ok(FlowStep::Continue(destination))
+        }
+        Ok(FlowResponse::Success(value)) => Err(KratosError::Unexpected(format!(
+            "Expecting a recovery flow or a redirect but got: {value}"
+        ))
+        .into()),
+        Err(err) => Err(err.into()),
+    }
+}
+
+impl KratosFlow for RecoveryFlow {
+    const TITLE: &'static str = "Recovery";
+
+    fn ui(&self) -> &UiContainer {
+        &self.ui
+    }
+    fn id(&self) -> &str {
+        &self.id
+    }
+
+    fn init(
+        return_to: Option<String>,
+        _query: ParamsMap,
+    ) -> impl Future<Output = FlowResult<Self>> {
+        init_recovery(return_to)
+    }
+    fn fetch(flow_id: String) -> impl Future<Output = FlowResult<Self>> {
+        fetch_preexisting_recovery_flow(flow_id)
+    }
+    fn submit(body: FlowBody) -> impl Future<Output = FlowResult<Self>> {
+        recover(body)
+    }
+}
+
+#[component]
+pub fn RecoveryPage() -> impl IntoView {
+    view! {<FlowPage<RecoveryFlow>/>}
+}
@@ -0,0 +1,183 @@
+use crate::auth::aal2_redirect;
+use crate::kratos_client::KratosClient;
+use crate::kratos_error::KratosError;
+use axum::{
+    async_trait,
+    extract::FromRequestParts,
+    http::{request::Parts, StatusCode},
+    response::{IntoResponse, Redirect, Response},
+};
+use axum_extra::extract::CookieJar;
+use ory_kratos_client::models::AuthenticatorAssuranceLevel;
+use serde::Deserialize;
+use thiserror::Error;
+use tracing::debug;
+
+/// The Kratos session of whoever made the request.
+/// Use it as an extractor in axum handlers or with `leptos_axum::extract::<Session>()` in server functions.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Session(pub ory_kratos_client::models::Session);
+
+impl std::ops::Deref for Session {
+    type Target = ory_kratos_client::models::Session;
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+#[derive(Clone, Debug, Error)]
+pub enum SessionError {
+    /// There's no session cookie or Kratos doesn't think it's valid.
+    #[error("Unauthorized")]
+    Unauthorized,
+    /// There's a session but it needs a second factor before it can be used.
+    #[error("Second factor required")]
+    Aal2Required { redirect_browser_to: Option<String> },
+    #[error("Kratos error: {0}")]
+    Kratos(String),
+}
+
+impl SessionError {
+    /// For server functions, KratosError's blanket From would make every SessionError Unexpected.
+    pub fn into_kratos_error(self) -> KratosError {
+        match self {
+            SessionError::Unauthorized => KratosError::SessionInactive,
+            SessionError::Aal2Required {
+                redirect_browser_to,
+            } => KratosError::Aal2Required {
+                redirect_browser_to,
+            },
+            SessionError::Kratos(message) => KratosError::Unexpected(message),
+        }
+    }
+
+    pub fn status_code(&self) -> StatusCode {
+        match self {
+            SessionError::Unauthorized => StatusCode::UNAUTHORIZED,
+            SessionError::Aal2Required { .. } => StatusCode::FORBIDDEN,
+            SessionError::Kratos(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+}
+
+impl IntoResponse for SessionError {
+    fn into_response(self) -> Response {
+        match self {
+            // the second factor step is a page, so the browser can be sent there instead.
+            SessionError::Aal2Required {
+                redirect_browser_to: Some(url),
+            } => Redirect::to(&url).into_response(),
+            err => (err.status_code(), err.to_string()).into_response(),
+        }
+    }
+}
+
+/// When whoami returns 403 (session_aal2_required) it tells us where to send the browser.
+#[derive(Debug, Deserialize)]
+struct Aal2Required {
+    redirect_browser_to: Option<String>,
+}
+
+impl Session {
+    /// Whether a second factor was used, sessions without an aal are aal1.
+    pub fn is_aal2(&self) -> bool {
+        matches!(
+            self.authenticator_assurance_level,
+            Some(AuthenticatorAssuranceLevel::Aal2 | AuthenticatorAssuranceLevel::Aal3)
+        )
+    }
+
+    /// https://www.ory.sh/docs/reference/api#tag/frontend/operation/toSession
+    pub async fn whoami(
+        kratos: &KratosClient,
+        cookie_jar: &CookieJar,
+    ) -> Result<Self, SessionError> {
+        let session_cookie = cookie_jar
+            .get("ory_kratos_session")
+            .ok_or(SessionError::Unauthorized)?;
+        let resp = kratos
+            .get("sessions/whoami")
+            .header(
+                "cookie",
+                format!("{}={}", session_cookie.name(), session_cookie.value()),
+            )
+            .send()
+            .await
+            .map_err(|err| SessionError::Kratos(err.to_string()))?;
+        debug!("{:#?}", resp);
+        match resp.status().as_u16() {
+            200 => Ok(Session(
+                resp.json::<ory_kratos_client::models::Session>()
+                    .await
+                    .map_err(|err| SessionError::Kratos(err.to_string()))?,
+            )),
+            401 => Err(SessionError::Unauthorized),
+            403 => {
+                let Aal2Required {
+                    redirect_browser_to,
+                } = resp
+                    .json::<Aal2Required>()
+                    .await
+                    .map_err(|err| SessionError::Kratos(err.to_string()))?;
+                Err(SessionError::Aal2Required {
+                    redirect_browser_to,
+                })
+            }
+            _ => Err(SessionError::Kratos(
+                resp.text()
+                    .await
+                    .map_err(|err| SessionError::Kratos(err.to_string()))?,
+            )),
+        }
+    }
+}
+
+#[async_trait]
+impl<S> FromRequestParts<S> for Session
+where
+    S: Send + Sync,
+{
+    type Rejection = SessionError;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        // main.rs adds it as an Extension, Leptos' context isn't available to extractors.
+        let kratos = parts
+            .extensions
+            .get::<KratosClient>()
+            .ok_or(SessionError::Kratos(String::from(
+                "Expecting KratosClient in the request extensions",
+            )))?;
+        let cookie_jar = CookieJar::from_headers(&parts.headers);
+        Session::whoami(kratos, &cookie_jar).await
+    }
+}
+
+/// A [Session] with a second factor, for handlers that require AAL2.
+/// aal1 sessions are rejected with a redirect to our login page's second factor step.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Aal2Session(pub Session);
+
+#[async_trait]
+impl<S> FromRequestParts<S> for Aal2Session
+where
+    S: Send + Sync,
+{
+    type Rejection = SessionError;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let session = Session::from_request_parts(parts, state).await?;
+        let config = parts
+            .extensions
+            .get::<KratosClient>()
+            .ok_or(SessionError::Kratos(String::from(
+                "Expecting KratosClient in the request extensions",
+            )))?
+            .config();
+        match aal2_redirect(config, &session, &parts.uri) {
+            Some(step_up) => Err(SessionError::Aal2Required {
+                redirect_browser_to: Some(step_up),
+            }),
+            None => Ok(Aal2Session(session)),
+        }
+    }
+}
@@ -0,0 +1,147 @@
+use super::*;
+
+#[cfg(feature = "ssr")]
+use crate::continue_with::{after_finished_flow, Destination};
+use crate::flow_page::{FlowPage, FlowResult, FlowStep, KratosFlow};
+#[cfg(feature = "ssr")]
+use crate::kratos_client::{FlowResponse, KratosClient};
+use crate::kratos_error::KratosError;
+use crate::kratos_utils::FlowBody;
+
+use ory_kratos_client::models::ui_node::GroupEnum;
+use ory_kratos_client::models::SettingsFlow;
+use ory_kratos_client::models::UiContainer;
+use std::future::Future;
+#[cfg(feature = "ssr")]
+use tracing::debug;
+
+/// The settings flow needs the session, so with no session at all we log in and come back.
+#[cfg(feature = "ssr")]
+fn login_for_settings(kratos: &KratosClient) -> FlowStep<SettingsFlow> {
+    let mut login_url = kratos.config().kratos_public("self-service/login/browser");
+    login_url
+        .query_pairs_mut()
+        .append_pair("return_to", kratos.config().base("settings").as_str());
+    FlowStep::Continue(Destination::Url(login_url.to_string()))
+}
+
+/// Kratos wants the browser somewhere else before it will let the user change their settings when
+/// there is no session or the session is older than privileged_session_max_age (a refresh login).
+#[cfg(feature = "ssr")]
+fn settings_response(
+    kratos: &KratosClient,
+    resp: Result<FlowResponse<SettingsFlow>, KratosError>,
+) -> Result<FlowStep<SettingsFlow>, ServerFnError<KratosError>> {
+    match resp {
+        Ok(FlowResponse::Flow(flow)) => {
+            debug!("{:#?}", flow);
+            Ok(FlowStep::Flow(flow))
+        }
+        Ok(FlowResponse::Success(value)) => Err(KratosError::Unexpected(format!(
+            "Expecting a settings flow but got: {value}"
+        ))
+        .into()),
+        Err(KratosError::SessionInactive) => Ok(login_for_settings(kratos)),
+        Err(
+            KratosError::RefreshRequired {
+                redirect_browser_to: Some(url),
+            }
+            | KratosError::Aal2Required {
+                redirect_browser_to: Some(url),
+            }
+            | KratosError::BrowserLocationChangeRequired {
+                redirect_browser_to: url,
+            },
+        ) => Ok(FlowStep::Continue(Destination::Url(url))),
+        Err(err) => Err(err.into()),
+    }
+}
+
+#[tracing::instrument]
+#[server]
+pub async fn init_settings(
+    return_to: Option<String>,
+) -> Result<FlowStep<SettingsFlow>, ServerFnError<KratosError>> {
+    let kratos = expect_context::<KratosClient>();
+    settings_response(
+        &kratos,
+        kratos
+            .create_browser_flow::<SettingsFlow>(return_to, &[])
+            .await,
+    )
+}
+
+#[tracing::instrument]
+#[server]
+pub async fn fetch_preexisting_settings_flow(
+    flow_id: String,
+) -> Result<FlowStep<SettingsFlow>, ServerFnError<KratosError>> {
+    let kratos = expect_context::<KratosClient>();
+    settings_response(&kratos, kratos.get_flow::<SettingsFlow>(&flow_id).await)
+}
+
+#[tracing::instrument]
+#[server(input = Json)]
+pub async fn update_settings(
+    body: FlowBody,
+) -> Result<FlowStep<SettingsFlow>, ServerFnError<KratosError>> {
+    let mut body = body;
+    let action = body
+        .remove("action")
+        .ok_or(KratosError::Unexpected(String::from(
+            "Can't find action on body.",
+        )))?;
+    let return_to = body.remove("return_to");
+    let kratos = expect_context::<KratosClient>();
+    let resp = kratos.submit_flow::<SettingsFlow>(&action, &body).await;
+    if let Ok(FlowResponse::Flow(flow)) = &resp {
+        if let Some(destination) = after_finished_flow(&kratos, flow, "success", return_to)? {
+            return Ok(FlowStep::Continue(destination));
+        }
+    }
+    settings_response(&kratos, resp)
+}
+
+impl KratosFlow for SettingsFlow {
+    const TITLE: &'static str = "Settings";
+    /// Each one is it's own form so that submitting one method doesn't send every other method's fields along with it.
+    const GROUPS: &'static [GroupEnum] = &[
+        GroupEnum::Profile,
+        GroupEnum::Password,
+        GroupEnum::Totp,
+        GroupEnum::LookupSecret,
+        GroupEnum::Webauthn,
+        GroupEnum::Passkey,
+    ];
+
+    fn ui(&self) -> &UiContainer {
+        &self.ui
+    }
+    fn id(&self) -> &str {
+        &self.id
+    }
+    fn return_to(&self) -> Option<String> {
+        self.return_to.clone()
+    }
+    fn schema_id(&self) -> Option<String> {
+        Some(self.identity.schema_id.clone())
+    }
+
+    fn init(
+        return_to: Option<String>,
+        _query: ParamsMap,
+    ) -> impl Future<Output = FlowResult<Self>> {
+        init_settings(return_to)
+    }
+    fn fetch(flow_id: String) -> impl Future<Output = FlowResult<Self>> {
+        fetch_preexisting_settings_flow(flow_id)
+    }
+    fn submit(body: FlowBody) -> impl Future<Output = FlowResult<Self>> {
+        update_settings(body)
+    }
+}
+
+#[component]
+pub fn SettingsPage() -> impl IntoView {
+    view! {<FlowPage<SettingsFlow>/>}
+}
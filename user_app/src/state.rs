@@ -0,0 +1,32 @@
+use crate::config::AppConfig;
+use crate::kratos_client::KratosClient;
+use axum::extract::FromRef;
+use leptos::LeptosOptions;
+
+#[derive(Clone, Debug)]
+pub struct AppState {
+    pub leptos_options: LeptosOptions,
+    pub config: AppConfig,
+    pub kratos: KratosClient,
+}
+
+// leptos_axum needs to find the LeptosOptions in our state.
+impl FromRef<AppState> for LeptosOptions {
+    fn from_ref(state: &AppState) -> Self {
+        state.leptos_options.clone()
+    }
+}
+
+impl FromRef<AppState> for AppConfig {
+    fn from_ref(state: &AppState) -> Self {
+        state.config.clone()
+    }
+}
+
+impl AppState {
+    /// Everything our server functions (and the components that call them on the server) expect as context.
+    pub fn provide_context(&self) {
+        leptos::provide_context(self.config.clone());
+        leptos::provide_context(self.kratos.clone());
+    }
+}
@@ -1,168 +1,111 @@
-use ory_kratos_client::models::{verification_flow, UiContainer, UiText, VerificationFlow};
+use ory_kratos_client::models::{UiContainer, VerificationFlow};
 
-use crate::kratos_utils::node_html;
-use tracing::debug;
 use super::*;
+#[cfg(feature = "ssr")]
+use crate::continue_with::{after_finished_flow, after_success};
+use crate::flow_page::{FlowPage, FlowResult, FlowStep, KratosFlow};
+#[cfg(feature = "ssr")]
+use crate::kratos_client::{FlowResponse, KratosClient};
+use crate::kratos_error::KratosError;
+use crate::kratos_utils::FlowBody;
+use std::future::Future;
+#[cfg(feature = "ssr")]
+use tracing::debug;
 
-#[derive(Clone, Debug, Serialize, Deserialize)]
-pub struct ViewableVerificationFlow(VerificationFlow);
-impl IntoView for ViewableVerificationFlow {
-    fn into_view(self) -> View {
-        format!("{:#?}", self.0).into_view()
-    }
+/// For when the user followed a link to /verification, Kratos' own emails link to ?flow=FLOW_ID
+#[tracing::instrument]
+#[server]
+pub async fn init_verification(
+    return_to: Option<String>,
+) -> Result<FlowStep<VerificationFlow>, ServerFnError<KratosError>> {
+    let kratos = expect_context::<KratosClient>();
+    let flow = kratos
+        .create_browser_flow::<VerificationFlow>(return_to, &[])
+        .await?
+        .into_flow()?;
+    debug!("{:#?}", flow);
+    Ok(FlowStep::Flow(flow))
 }
-// https://{project}.projects.oryapis.com/self-service/verification/flows?id={}
+
+// https://www.ory.sh/docs/reference/api#tag/frontend/operation/getVerificationFlow
 #[tracing::instrument]
 #[server]
-pub async fn init_verification(flow_id: String) -> Result<Option<ViewableVerificationFlow>, ServerFnError> {
-    let cookie_jar = leptos_axum::extract::<axum_extra::extract::CookieJar>().await?;
-    let csrf_cookie = cookie_jar
-        .iter()
-        .filter(|cookie| cookie.name().contains("csrf_token"))
-        .next()
-        .ok_or(ServerFnError::new(
-            "Expecting a csrf_token cookie to already be set if fetching a pre-existing flow",
-        ))?;
-    let csrf_token = csrf_cookie.value();
-    let client = reqwest::ClientBuilder::new()
-        .redirect(reqwest::redirect::Policy::none())
-        .build()?;
-    // https://www.ory.sh/docs/reference/api#tag/frontend/operation/getVerificationFlow
-    let resp = client
-        .get("http://127.0.0.1:4433/self-service/verification/flows")
-        .query(&[("id", flow_id)])
-        //.header("x-csrf-token", csrf_token)
-        //.header("content-type","application/json")
-        .header(
-            "cookie",
-            format!("{}={}", csrf_cookie.name(), csrf_cookie.value()),
-        )
-        .send()
-        .await?;
-    debug!("{:#?}", resp);
-    if resp.status().as_u16() == 403 {
-        debug!("{:#?}", resp.text().await?);
-        Ok(None)
-    } else {
-        let flow = resp.json::<ViewableVerificationFlow>().await?;
-        debug!("{:#?}", flow);
-        Ok(Some(flow))
-    }
+pub async fn fetch_preexisting_verification_flow(
+    flow_id: String,
+) -> Result<FlowStep<VerificationFlow>, ServerFnError<KratosError>> {
+    let kratos = expect_context::<KratosClient>();
+    let flow = kratos
+        .get_flow::<VerificationFlow>(&flow_id)
+        .await?
+        .into_flow()?;
+    debug!("{:#?}", flow);
+    Ok(FlowStep::Flow(flow))
 }
+
 // verification flow complete POST
 //http://127.0.0.1:4433/self-service/verification
 #[tracing::instrument]
-#[server]
+#[server(input = Json)]
 pub async fn verify(
-    body:HashMap<String,String>
-) -> Result<Option<ViewableVerificationFlow>, ServerFnError> {
+    body: FlowBody,
+) -> Result<FlowStep<VerificationFlow>, ServerFnError<KratosError>> {
     let mut body = body;
     let action = body
         .remove("action")
-        .ok_or(ServerFnError::new("Can't find action on body."))?;
-    let cookie_jar = leptos_axum::extract::<axum_extra::extract::CookieJar>().await?;
-    let csrf_cookie = cookie_jar
-        .iter()
-        .filter(|cookie| cookie.name().contains("csrf_token"))
-        .next()
-        .ok_or(ServerFnError::new(
-            "Expecting a csrf_token cookie to already be set if fetching a pre-existing flow",
-        ))?;
-    let csrf_token = csrf_cookie.value();
-    let client = reqwest::ClientBuilder::new()
-        .redirect(reqwest::redirect::Policy::none())
-        .build()?;
-    let resp = client
-        .post(action)
-        .header("x-csrf-token", csrf_token)
-        .header("content-type", "application/json")
-        .header("accept","application/json")
-        .header(
-            "cookie",
-            format!("{}={}", csrf_cookie.name(), csrf_cookie.value()),
-        )
-        .body(serde_json::to_string(&body)?)
-        .send()
-        .await?;
-    debug!("{:#?}", resp);
+        .ok_or(KratosError::Unexpected(String::from(
+            "Can't find action on body.",
+        )))?;
+    let return_to = body.remove("return_to");
+    let kratos = expect_context::<KratosClient>();
+    match kratos
+        .submit_flow::<VerificationFlow>(&action, &body)
+        .await?
+    {
+        // including when it's done, then it's state is passed_challenge and it has a continue link.
+        FlowResponse::Flow(flow) => {
+            debug!("{:#?}", flow);
+            match after_finished_flow(&kratos, &flow, "passed_challenge", return_to)? {
+                Some(destination) => Ok(FlowStep::Continue(destination)),
+                None => Ok(FlowStep::Flow(flow)),
+            }
+        }
+        FlowResponse::Success(value) => {
+            debug!("{:#?}", value);
+            Ok(FlowStep::Continue(after_success(
+                &kratos, &value, return_to,
+            )))
+        }
+    }
+}
 
-    let opts = expect_context::<leptos_axum::ResponseOptions>();
-    opts.insert_header(
-        axum::http::HeaderName::from_static("cache-control"),
-        axum::http::HeaderValue::from_str("private, no-cache, no-store, must-revalidate")?,
-    );
-    if resp.status().as_u16() == 403 {
-        debug!("{:#?}", resp.text().await?);
-        Ok(None)
-    } else {
-        let flow = resp.json::<ViewableVerificationFlow>().await?;
-        debug!("{:#?}", flow);
-        Ok(Some(flow))
-    }}
+impl KratosFlow for VerificationFlow {
+    const TITLE: &'static str = "Verification";
 
-#[component]
-pub fn VerificationPage() -> impl IntoView {
-    let verify = Action::<Verify, _>::server();
+    fn ui(&self) -> &UiContainer {
+        &self.ui
+    }
+    fn id(&self) -> &str {
+        &self.id
+    }
+    fn return_to(&self) -> Option<String> {
+        self.return_to.clone()
+    }
 
-    let params_map = use_query_map();
-    let init_verification = create_local_resource(
-        move || params_map().get("flow").cloned().unwrap_or_default(),
-        |flow_id| async move { init_verification(flow_id).await },
-    );
-    let verfication_resp =
-        create_rw_signal(None::<Result<Option<ViewableVerificationFlow>, ServerFnError>>);
-    create_effect(move |_| {
-        if let Some(resp) = verify.value().get() {
-            verfication_resp.set(Some(resp))
-        }
-    });
-    let verification_flow = Signal::derive(move || {
-        if let Some(flow) = verfication_resp.get() {
-            Some(flow)
-        } else {
-            init_verification
-                .get()
-        }
-    });
-    let body = create_rw_signal(HashMap::new());
-    view! {
-        <Suspense fallback=||view!{Loading Login Details}>
-        <ErrorBoundary fallback=|errors|format!("ERRORS: {:?}",errors.get()).into_view()>
-        {
-          move ||
-          verification_flow.get().map(|resp|{
-                match resp {
-                    Ok(Some(ViewableVerificationFlow(VerificationFlow{ui:box UiContainer{nodes,messages,action,..},..}))) => {
-                            let form_inner_html = nodes.into_iter().map(|node|node_html(node,body)).collect_view();
-                            body.update(|map|{_=map.insert(String::from("action"),action);});
-                            view!{
-                                <form on:submit=move|e|{
-                                    e.prevent_default();
-                                    e.stop_propagation();
-                                    verify.dispatch(Verify{body:body.get_untracked()});
-                                }>
-                                {form_inner_html}
-                                {messages.map(|messages|{
-                                    view!{
-                                        <For
-                                            each=move || messages.clone().into_iter()
-                                            key=|text| text.id
-                                            children=move |text: UiText| {
-                                              view! {
-                                                <p id=text.id>{text.text}</p>
-                                              }
-                                            }
-                                        />
-                                    }
-                                }).unwrap_or_default()}
-                                </form>
-                            }.into_view()
-                    },
-                    err => err.into_view(),
-                }
-            })
-          }
-        </ErrorBoundary>
-      </Suspense>
+    fn init(
+        return_to: Option<String>,
+        _query: ParamsMap,
+    ) -> impl Future<Output = FlowResult<Self>> {
+        init_verification(return_to)
     }
+    fn fetch(flow_id: String) -> impl Future<Output = FlowResult<Self>> {
+        fetch_preexisting_verification_flow(flow_id)
+    }
+    fn submit(body: FlowBody) -> impl Future<Output = FlowResult<Self>> {
+        verify(body)
+    }
+}
+
+#[component]
+pub fn VerificationPage() -> impl IntoView {
+    view! {<FlowPage<VerificationFlow>/>}
 }